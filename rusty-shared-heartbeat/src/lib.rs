@@ -0,0 +1,133 @@
+#![warn(
+    clippy::all,
+    clippy::missing_const_for_fn,
+    clippy::trivially_copy_pass_by_ref,
+    clippy::map_unwrap_or,
+    clippy::explicit_into_iter_loop,
+    clippy::unused_self,
+    clippy::needless_pass_by_value
+)]
+
+//! Rate-limited heartbeat, shared by the microservices.
+
+pub mod status;
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{anyhow, Error, Result};
+use futures::future::join_all;
+use governor::{DefaultDirectRateLimiter, Quota, RateLimiter};
+use reqwest::{Client, Url};
+use rusty_shared_opts::heartbeat::Opts;
+use rusty_shared_opts::systemd;
+use tracing::{debug, error, instrument, warn};
+
+use crate::status::ServiceStatus;
+
+#[derive(Clone)]
+pub struct Heartbeat {
+    client: Client,
+
+    /// URLs to post to on the heartbeat.
+    urls: Vec<Url>,
+
+    /// URLs to post to on a failure.
+    failure_urls: Vec<Url>,
+
+    /// Status, which tracks the last activity, once there's one.
+    status: Option<Arc<ServiceStatus>>,
+
+    limiter: Arc<DefaultDirectRateLimiter>,
+}
+
+impl Heartbeat {
+    /// Timeout of each heartbeat and failure request.
+    const TIMEOUT: Duration = Duration::from_secs(10);
+
+    /// Create the heartbeat, which may already report the failures,
+    /// even before the status is available.
+    pub fn new(opts: &Opts) -> Result<Self> {
+        if opts.heartbeat_urls.is_empty() {
+            warn!("heartbeat URL is not specified, heartbeat is disabled");
+        }
+        let quota = Quota::with_period(Duration::from_secs(opts.heartbeat_interval_secs))
+            .ok_or_else(|| anyhow!("the heartbeat interval must be positive"))?;
+        Ok(Self {
+            client: Client::builder().timeout(Self::TIMEOUT).build()?,
+            urls: parse_urls(&opts.heartbeat_urls)?,
+            failure_urls: parse_urls(&opts.heartbeat_failure_urls)?,
+            status: None,
+            limiter: Arc::new(RateLimiter::direct(quota)),
+        })
+    }
+
+    /// Track the activity in the status.
+    pub fn with_status(mut self, status: Arc<ServiceStatus>) -> Self {
+        self.status = Some(status);
+        self
+    }
+
+    /// Ping the systemd watchdog, update the status,
+    /// and send the heartbeat unless it's been recently sent.
+    #[instrument(skip_all)]
+    pub async fn send(&self) {
+        systemd::notify_watchdog();
+        if let Some(status) = &self.status {
+            status.on_activity();
+        }
+        if self.limiter.check().is_err() {
+            debug!("skipping the heartbeat");
+            return;
+        }
+        debug!("sending heartbeat…");
+        for url in &self.urls {
+            if let Err(error) = self.client.post(url.clone()).send().await {
+                warn!(%url, "heartbeat error: {:#}", error);
+            }
+        }
+    }
+
+    /// Report the fatal error to the failure URLs, bypassing the rate limit.
+    ///
+    /// The URLs are posted to concurrently, so that it takes at most the request timeout.
+    #[instrument(skip_all)]
+    pub async fn fail(&self, error: &Error) {
+        let body = &format!("{:#}", error);
+        join_all(self.failure_urls.iter().map(|url| async move {
+            warn!(%url, "reporting the failure…");
+            let result = self
+                .client
+                .post(url.clone())
+                .body(body.clone())
+                .send()
+                .await;
+            if let Err(error) = result {
+                error!(%url, "failed to report the failure: {:#}", error);
+            }
+        }))
+        .await;
+    }
+}
+
+fn parse_urls(urls: &[String]) -> Result<Vec<Url>> {
+    urls.iter()
+        .map(|url| {
+            url.parse()
+                .map_err(|error| anyhow!("invalid URL `{}`: {}", url, error))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_urls_ok() -> Result<()> {
+        let urls = parse_urls(&["https://hc-ping.com/uuid".to_string()])?;
+        assert_eq!(urls[0].as_str(), "https://hc-ping.com/uuid");
+        assert!(parse_urls(&["not a URL".to_string()]).is_err());
+        Ok(())
+    }
+}
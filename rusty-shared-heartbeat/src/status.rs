@@ -0,0 +1,204 @@
+//! Cluster status: each running instance publishes its `rusty:services:<service>:<host>` hash.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use fred::prelude::*;
+use fred::types::RedisKey;
+use futures::StreamExt;
+use gethostname::gethostname;
+use kv_derive::prelude::*;
+use kv_derive::{FromMapping, IntoVec};
+use rusty_shared_opts::shutdown::Shutdown;
+use rusty_shared_redis::Redis;
+use rusty_shared_time::now;
+use tracing::{debug, info, instrument, warn};
+
+/// Status of a running instance.
+#[derive(IntoVec, FromMapping, Debug, PartialEq, Eq)]
+pub struct Instance {
+    #[kv(rename = "service")]
+    pub service_name: String,
+
+    pub hostname: String,
+    pub version: String,
+
+    /// Unix time.
+    pub started_at: i64,
+
+    /// Unix time of the last heartbeat, if any.
+    #[kv(optional, default())]
+    pub last_activity_at: Option<i64>,
+
+    /// Whether the instance holds the `rusty:leaders:<service>` lease.
+    pub is_leader: bool,
+}
+
+/// Publishes the instance status.
+pub struct ServiceStatus {
+    redis: Redis,
+    key: RedisKey,
+    leader_key: RedisKey,
+    service_name: String,
+    hostname: String,
+    version: String,
+    started_at: i64,
+    ttl: Duration,
+
+    /// Unix time of the last heartbeat, zero until there's one.
+    last_activity_at: AtomicI64,
+}
+
+impl ServiceStatus {
+    const KEY_PATTERN: &'static str = "rusty:services:*";
+
+    pub fn new(redis: Redis, service_name: &str, version: &str, ttl: Duration) -> Self {
+        let hostname = gethostname().to_string_lossy().into_owned();
+        Self {
+            redis,
+            key: RedisKey::from(format!("rusty:services:{}:{}", service_name, hostname)),
+            leader_key: RedisKey::from(format!("rusty:leaders:{}", service_name)),
+            service_name: service_name.to_string(),
+            hostname,
+            version: version.to_string(),
+            started_at: now(),
+            ttl,
+            last_activity_at: AtomicI64::new(0),
+        }
+    }
+
+    /// Called on each heartbeat.
+    pub fn on_activity(&self) {
+        self.last_activity_at.store(now(), Ordering::Relaxed);
+    }
+
+    /// Refresh the status until the shutdown is requested, and then remove it.
+    pub async fn run(&self, shutdown: &Shutdown) -> Result<()> {
+        info!(key = ?self.key.as_str(), ttl = ?self.ttl, "publishing the status…");
+        loop {
+            if let Err(error) = self.refresh().await {
+                warn!("failed to refresh the status: {:#}", error);
+            }
+            if !shutdown.sleep(self.ttl / 3).await {
+                break;
+            }
+        }
+        self.remove().await
+    }
+
+    #[instrument(skip_all)]
+    async fn refresh(&self) -> Result<()> {
+        let is_leader = self
+            .redis
+            .acquire_lease(&self.leader_key, &self.hostname, self.ttl)
+            .await?;
+        let instance = Instance {
+            service_name: self.service_name.clone(),
+            hostname: self.hostname.clone(),
+            version: self.version.clone(),
+            started_at: self.started_at,
+            last_activity_at: Some(self.last_activity_at.load(Ordering::Relaxed))
+                .filter(|timestamp| *timestamp != 0),
+            is_leader,
+        };
+        debug!(?instance, "refreshing the status…");
+        // The fields only get added, so there's no need to delete the hash first.
+        self.redis
+            .hset_with_ttl(&self.key, instance.into_vec(), self.ttl.as_secs())
+            .await
+    }
+
+    #[instrument(skip_all)]
+    async fn remove(&self) -> Result<()> {
+        info!("removing the status…");
+        self.redis
+            .delete_if_equal(&self.leader_key, &self.hostname)
+            .await?;
+        self.redis.pool.del::<(), _>(&self.key).await?;
+        Ok(())
+    }
+
+    /// List the running instances of all the services, ordered by the service and host names.
+    #[instrument(skip_all)]
+    pub async fn list(redis: &Redis) -> Result<Vec<Instance>> {
+        let mut keys = Vec::new();
+        let mut pages = redis.pool.next().scan(Self::KEY_PATTERN, Some(100), None);
+        while let Some(page) = pages.next().await {
+            let mut page = page?;
+            keys.extend(page.take_results().unwrap_or_default());
+            page.next()?;
+        }
+
+        let mut instances = Vec::with_capacity(keys.len());
+        for key in keys {
+            let fields: HashMap<String, String> = redis.pool.hgetall(&key).await?;
+            if fields.is_empty() {
+                // The hash has expired meanwhile.
+                continue;
+            }
+            match Instance::from_mapping(fields).context("failed to parse the status") {
+                Ok(instance) => instances.push(instance),
+                // Don't let a single bad hash hide the rest of the cluster.
+                Err(error) => warn!(key = ?key.as_str(), "skipping the status: {:#}", error),
+            }
+        }
+        instances.sort_by(|lhs, rhs| {
+            (&lhs.service_name, &lhs.hostname).cmp(&(&rhs.service_name, &rhs.hostname))
+        });
+        Ok(instances)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn instance_round_trip_ok() -> Result<()> {
+        let instance = Instance {
+            service_name: "rusty-tractive".to_string(),
+            hostname: "pi-1".to_string(),
+            version: "0.0.0".to_string(),
+            started_at: 1_660_000_000,
+            last_activity_at: None,
+            is_leader: true,
+        };
+        let fields: HashMap<String, String> = instance.into_vec().into_iter().collect();
+        assert_eq!(fields["service"], "rusty-tractive");
+        assert!(!fields.contains_key("last_activity_at"));
+        assert_eq!(
+            Instance::from_mapping(fields)?,
+            Instance {
+                service_name: "rusty-tractive".to_string(),
+                hostname: "pi-1".to_string(),
+                version: "0.0.0".to_string(),
+                started_at: 1_660_000_000,
+                last_activity_at: None,
+                is_leader: true,
+            },
+        );
+        Ok(())
+    }
+
+    #[async_std::test]
+    #[ignore = "requires Redis, see `RUSTY_HOME_TEST_REDIS_URL`"]
+    async fn leader_lease_ok() -> Result<()> {
+        let redis_url = std::env::var("RUSTY_HOME_TEST_REDIS_URL")
+            .unwrap_or_else(|_| "redis://localhost".to_string());
+        let redis = Redis::connect(&redis_url, "test").await?;
+        let key = format!("rusty:leaders:test-{}-{}", std::process::id(), now());
+        let ttl = Duration::from_secs(60);
+
+        assert!(redis.acquire_lease(key.as_str(), "pi-1", ttl).await?);
+        assert!(!redis.acquire_lease(key.as_str(), "pi-2", ttl).await?);
+        assert!(redis.acquire_lease(key.as_str(), "pi-1", ttl).await?);
+
+        assert!(!redis.delete_if_equal(key.as_str(), "pi-2").await?);
+        assert!(redis.delete_if_equal(key.as_str(), "pi-1").await?);
+        assert!(redis.acquire_lease(key.as_str(), "pi-2", ttl).await?);
+        redis.pool.del::<(), _>(key.as_str()).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,64 @@
+#![warn(
+    clippy::all,
+    clippy::missing_const_for_fn,
+    clippy::trivially_copy_pass_by_ref,
+    clippy::map_unwrap_or,
+    clippy::explicit_into_iter_loop,
+    clippy::unused_self,
+    clippy::needless_pass_by_value
+)]
+
+//! Prometheus metrics, shared by the microservices.
+//!
+//! The metrics get registered in the default registry,
+//! so that the crates just declare them with the `prometheus::register_*!` macros.
+
+use std::future::Future;
+
+use anyhow::{Context, Result};
+use poem::listener::TcpListener;
+use poem::{get, handler, Route, Server};
+pub use prometheus::{
+    register_histogram_vec, register_int_counter, register_int_counter_vec, HistogramVec,
+    IntCounter, IntCounterVec,
+};
+use prometheus::{Encoder, TextEncoder};
+use tracing::{info, instrument};
+
+/// Encode the registered metrics in the Prometheus text format.
+pub fn encode() -> Result<String> {
+    let mut buffer = Vec::new();
+    TextEncoder::new().encode(&prometheus::gather(), &mut buffer)?;
+    Ok(String::from_utf8(buffer)?)
+}
+
+/// Serves the registered metrics.
+#[handler]
+#[instrument(skip_all)]
+pub fn get_metrics() -> Result<String> {
+    encode()
+}
+
+/// Run the standalone `/metrics` server, for the microservices which don't have their own.
+///
+/// The server stops when the shutdown signal completes.
+pub async fn serve(bind_endpoint: &str, shutdown: impl Future<Output = ()>) -> Result<()> {
+    info!(bind_endpoint, "serving the metrics…");
+    Server::new(TcpListener::bind(bind_endpoint))
+        .run_with_graceful_shutdown(Route::new().at("/metrics", get(get_metrics)), shutdown, None)
+        .await
+        .context("the metrics server has failed")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encode_ok() -> Result<()> {
+        let counter = register_int_counter!("rusty_test_total", "Test counter")?;
+        counter.inc();
+        assert!(encode()?.contains("rusty_test_total 1"));
+        Ok(())
+    }
+}
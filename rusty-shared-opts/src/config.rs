@@ -0,0 +1,307 @@
+//! Optional TOML configuration file.
+//!
+//! The file is layered under the environment variables and the command-line flags:
+//! each setting is named after its flag or the flag's alias, nested tables get joined with `-`.
+//! Multiple values are specified as arrays. For example:
+//!
+//! ```toml
+//! zones = ["home:Home=52.3676,4.9041,150"]
+//!
+//! [chat]
+//! id = 42
+//!
+//! [battery]
+//! full-message = "🔋 *{current_level}%* Battery is now full\\!"
+//! ```
+
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::{env, fs};
+
+use anyhow::{anyhow, bail, Context, Result};
+use clap::{Arg, Command, Parser};
+use toml::Value;
+
+#[derive(Parser)]
+pub struct Opts {
+    /// Optional TOML configuration file.
+    /// The environment variables and the flags take precedence over it.
+    #[clap(long = "config", env = "RUSTY_HOME_CONFIG")]
+    pub config_path: Option<PathBuf>,
+}
+
+impl Opts {
+    pub const ENV: &'static str = "RUSTY_HOME_CONFIG";
+}
+
+/// Parse the options, taking the configuration file into account.
+///
+/// The `Opts` must flatten [`Opts`], so that `--config` is accepted.
+pub fn parse<T: Parser>() -> Result<T> {
+    let args: Vec<OsString> = env::args_os().collect();
+    let config = match find_config_path(&args) {
+        Some(path) => Config::read(&path)?,
+        None => Config::default(),
+    };
+    Ok(T::parse_from(config.merge_args(&T::command(), &args)?))
+}
+
+/// Find the configuration path in the flags, or in the environment.
+fn find_config_path(args: &[OsString]) -> Option<PathBuf> {
+    let mut args = args.iter().skip(1);
+    while let Some(arg) = args.next() {
+        let arg = arg.to_string_lossy();
+        if arg == "--config" {
+            return args.next().map(PathBuf::from);
+        }
+        if let Some(path) = arg.strip_prefix("--config=") {
+            return Some(PathBuf::from(path));
+        }
+    }
+    env::var_os(Opts::ENV).map(PathBuf::from)
+}
+
+/// Flattened settings, keyed by the `-`-separated names.
+#[derive(Debug, Default, PartialEq)]
+pub struct Config(BTreeMap<String, Value>);
+
+impl Config {
+    /// Read the configuration file.
+    pub fn read(path: &Path) -> Result<Self> {
+        let read =
+            || -> Result<Self> { Self::try_from(fs::read_to_string(path)?.parse::<Value>()?) };
+        read().with_context(|| format!("invalid configuration file `{}`", path.display()))
+    }
+
+    /// Parse the options, like [`parse`] does, but with this configuration.
+    ///
+    /// Unlike [`parse`], it returns an error instead of exiting.
+    pub fn try_parse<T: Parser>(&self) -> Result<T> {
+        let args: Vec<OsString> = env::args_os().collect();
+        Ok(T::try_parse_from(self.merge_args(&T::command(), &args)?)?)
+    }
+
+    /// Insert the settings into the flags,
+    /// unless they're already set by the flags or the environment variables.
+    fn merge_args(&self, command: &Command, args: &[OsString]) -> Result<Vec<OsString>> {
+        let mut config_args = Vec::new();
+        for (key, value) in &self.0 {
+            let arg = command
+                .get_arguments()
+                .find(|arg| is_named(arg, key))
+                .ok_or_else(|| anyhow!("unknown setting `{}`", key))?;
+            let long = arg
+                .get_long()
+                .ok_or_else(|| anyhow!("`{}` can't be set in the configuration", key))?;
+            if is_set(arg, args) {
+                continue;
+            }
+            match value {
+                Value::Array(items) => {
+                    if !arg.is_multiple_occurrences_set() {
+                        bail!("`{}` doesn't accept multiple values", key);
+                    }
+                    for item in items {
+                        config_args.push(format!("--{}={}", long, to_string(key, item)?));
+                    }
+                }
+                Value::Boolean(value) if !arg.is_takes_value_set() => {
+                    if *value {
+                        config_args.push(format!("--{}", long));
+                    }
+                }
+                value => config_args.push(format!("--{}={}", long, to_string(key, value)?)),
+            }
+        }
+
+        let mut args = args.iter().cloned();
+        Ok(args
+            .next()
+            .into_iter()
+            .chain(config_args.into_iter().map(OsString::from))
+            .chain(args)
+            .collect())
+    }
+
+    /// Flatten the nested tables into the `-`-separated keys.
+    fn flatten(&mut self, prefix: &str, value: Value) -> Result<()> {
+        match value {
+            Value::Table(table) => {
+                for (key, value) in table {
+                    let key = key.replace('_', "-");
+                    let key = if prefix.is_empty() {
+                        key
+                    } else {
+                        format!("{}-{}", prefix, key)
+                    };
+                    self.flatten(&key, value)?;
+                }
+            }
+            value if prefix.is_empty() => bail!("expected a table, got `{}`", value),
+            value => {
+                self.0.insert(prefix.to_string(), value);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl TryFrom<Value> for Config {
+    type Error = anyhow::Error;
+
+    fn try_from(value: Value) -> Result<Self> {
+        let mut config = Self::default();
+        config.flatten("", value)?;
+        Ok(config)
+    }
+}
+
+/// Overrides the settings with the plain string values.
+impl Extend<(String, String)> for Config {
+    fn extend<I: IntoIterator<Item = (String, String)>>(&mut self, iter: I) {
+        for (key, value) in iter {
+            self.0.insert(key.replace('_', "-"), Value::String(value));
+        }
+    }
+}
+
+fn is_named(arg: &Arg, name: &str) -> bool {
+    arg.get_long() == Some(name)
+        || arg
+            .get_all_aliases()
+            .is_some_and(|aliases| aliases.contains(&name))
+}
+
+/// Check whether the argument is set by the flags or its environment variable.
+fn is_set(arg: &Arg, args: &[OsString]) -> bool {
+    if arg
+        .get_env()
+        .is_some_and(|name| env::var_os(name).is_some())
+    {
+        return true;
+    }
+    args.iter().skip(1).any(|flag| {
+        let flag = flag.to_string_lossy();
+        flag.strip_prefix("--")
+            .map(|flag| flag.split_once('=').map_or(flag, |(name, _)| name))
+            .is_some_and(|name| is_named(arg, name))
+    })
+}
+
+fn to_string(key: &str, value: &Value) -> Result<String> {
+    match value {
+        Value::String(value) => Ok(value.clone()),
+        Value::Integer(value) => Ok(value.to_string()),
+        Value::Float(value) => Ok(value.to_string()),
+        Value::Boolean(value) => Ok(value.to_string()),
+        Value::Datetime(value) => Ok(value.to_string()),
+        Value::Array(_) | Value::Table(_) => bail!("`{}` must be a plain value", key),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::CommandFactory;
+
+    use super::*;
+
+    #[derive(Parser)]
+    struct TestOpts {
+        #[clap(long = "chat-id", env = "TEST_CHAT_ID")]
+        _chat_id: i64,
+
+        #[clap(long = "battery-full-message", env = "TEST_BATTERY_FULL_MESSAGE")]
+        _full_message: String,
+
+        #[clap(
+            long = "zone",
+            alias = "zones",
+            env = "TEST_ZONES",
+            multiple_occurrences = true,
+            value_delimiter = '|'
+        )]
+        _zones: Vec<String>,
+
+        #[clap(long = "verbose")]
+        _verbose: bool,
+    }
+
+    fn merge_args(toml: &str, args: &[&str]) -> Result<Vec<String>> {
+        let config = Config::try_from(toml.parse::<Value>()?)?;
+        let args: Vec<_> = args.iter().map(OsString::from).collect();
+        Ok(config
+            .merge_args(&TestOpts::command(), &args)?
+            .into_iter()
+            .map(|arg| arg.into_string().unwrap())
+            .collect())
+    }
+
+    #[test]
+    fn merge_args_ok() -> Result<()> {
+        let args = merge_args(
+            // language=toml
+            r#"
+                zones = ["home:Home=1,2,3", "danger:Road=4,5,6"]
+                verbose = true
+
+                [chat]
+                id = 42
+
+                [battery]
+                full-message = "Full!"
+            "#,
+            &["bin", "--battery-full-message", "Overridden!"],
+        )?;
+        assert_eq!(
+            args,
+            vec![
+                "bin",
+                "--chat-id=42",
+                "--verbose",
+                "--zone=home:Home=1,2,3",
+                "--zone=danger:Road=4,5,6",
+                "--battery-full-message",
+                "Overridden!",
+            ],
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn extend_ok() -> Result<()> {
+        let mut config = Config::try_from("chat.id = 42".parse::<Value>()?)?;
+        config.extend([("chat_id".to_string(), "43".to_string())]);
+        assert_eq!(merge_args_of(&config)?, ["bin", "--chat-id=43"]);
+        Ok(())
+    }
+
+    fn merge_args_of(config: &Config) -> Result<Vec<OsString>> {
+        config.merge_args(&TestOpts::command(), &[OsString::from("bin")])
+    }
+
+    #[test]
+    fn unknown_setting_error() {
+        let error = merge_args("[battery]\nfull-massage = \"Full!\"", &["bin"]).unwrap_err();
+        assert_eq!(error.to_string(), "unknown setting `battery-full-massage`");
+    }
+
+    #[test]
+    fn invalid_settings_error() {
+        assert!(merge_args("chat-id = [1, 2]", &["bin"]).is_err());
+        assert!(merge_args("zone = [[1]]", &["bin"]).is_err());
+    }
+
+    #[test]
+    fn find_config_path_ok() {
+        let args = |args: &[&str]| args.iter().map(OsString::from).collect::<Vec<_>>();
+        assert_eq!(
+            find_config_path(&args(&["bin", "--config", "a.toml"])),
+            Some(PathBuf::from("a.toml")),
+        );
+        assert_eq!(
+            find_config_path(&args(&["bin", "--config=b.toml"])),
+            Some(PathBuf::from("b.toml")),
+        );
+    }
+}
@@ -1,49 +1,59 @@
-use anyhow::Result;
 use clap::Parser;
-use reqwest::{Client, Url};
-use tracing::{debug, instrument, warn};
 
 #[derive(Parser)]
 pub struct Opts {
     /// URL to which the microservice should post its heartbeat.
-    #[clap(long = "heartbeat-url", env = "RUSTY_HOME_HEARTBEAT_URL")]
-    heartbeat_url: Option<String>,
-}
+    #[clap(
+        long = "heartbeat-url",
+        alias = "heartbeat-urls",
+        env = "RUSTY_HOME_HEARTBEAT_URL",
+        multiple_occurrences = true,
+        value_delimiter = ','
+    )]
+    pub heartbeat_urls: Vec<String>,
 
-impl Opts {
-    pub fn get_heartbeat(self) -> Result<Heartbeat> {
-        if self.heartbeat_url.is_none() {
-            warn!("heartbeat URL is not specified, heartbeat is disabled");
-        }
-        self.heartbeat_url.map_or_else(
-            || Ok(Heartbeat::new(None)),
-            |url| Ok(Heartbeat::new(Some((Client::new(), url.parse()?)))),
-        )
-    }
-}
+    /// URL to which the microservice should post when it fails,
+    /// for example, `https://hc-ping.com/<uuid>/fail`.
+    #[clap(
+        long = "heartbeat-failure-url",
+        alias = "heartbeat-failure-urls",
+        env = "RUSTY_HOME_HEARTBEAT_FAILURE_URL",
+        multiple_occurrences = true,
+        value_delimiter = ','
+    )]
+    pub heartbeat_failure_urls: Vec<String>,
 
-/// TODO: extract to a separate package. Use `governor`.
-pub struct Heartbeat {
-    endpoint: Option<(Client, Url)>,
+    /// Minimal interval between the heartbeats, in seconds. The more frequent ones are skipped.
+    #[clap(
+        long = "heartbeat-interval",
+        env = "RUSTY_HOME_HEARTBEAT_INTERVAL",
+        default_value = "60"
+    )]
+    pub heartbeat_interval_secs: u64,
+
+    /// TTL of the `rusty:services:<service>:<host>` status hash in Redis, in seconds.
+    /// The hash gets refreshed three times per TTL, so the TTL must be positive.
+    #[clap(
+        long = "status-ttl",
+        env = "RUSTY_HOME_STATUS_TTL",
+        default_value = "60",
+        value_parser = clap::value_parser!(u64).range(1..)
+    )]
+    pub status_ttl_secs: u64,
 }
 
-impl Heartbeat {
-    pub const fn new(endpoint: Option<(Client, Url)>) -> Self {
-        Self { endpoint }
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn zero_status_ttl_error() {
+        assert!(Opts::try_parse_from(["test", "--status-ttl", "0"]).is_err());
     }
 
-    #[instrument(skip_all)]
-    pub async fn send(&self) {
-        match &self.endpoint {
-            Some((client, url)) => {
-                debug!("sending heartbeat…");
-                if let Err(error) = client.post(url.clone()).send().await {
-                    warn!("heartbeat error: {:#}", error);
-                }
-            }
-            None => {
-                debug!("heartbeat is disabled");
-            }
-        };
+    #[test]
+    fn status_ttl_ok() -> Result<(), clap::Error> {
+        assert_eq!(Opts::try_parse_from(["test", "--status-ttl", "30"])?.status_ttl_secs, 30);
+        Ok(())
     }
 }
@@ -8,6 +8,12 @@
     clippy::needless_pass_by_value
 )]
 
+pub mod config;
 pub mod heartbeat;
+pub mod log;
+pub mod metrics;
+pub mod otlp;
 pub mod redis;
 pub mod sentry;
+pub mod shutdown;
+pub mod systemd;
@@ -0,0 +1,25 @@
+use clap::{Parser, ValueEnum};
+
+#[derive(Parser, Copy, Clone)]
+pub struct Opts {
+    /// Log output format. The verbosity is controlled by `RUSTY_HOME_LOG`.
+    #[clap(
+        long = "log-format",
+        env = "RUSTY_HOME_LOG_FORMAT",
+        value_enum,
+        default_value = "pretty"
+    )]
+    pub log_format: LogFormat,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ValueEnum)]
+pub enum LogFormat {
+    /// Human-readable lines.
+    Pretty,
+
+    /// JSON lines with the span context.
+    Json,
+
+    /// Native journald entries with the structured fields.
+    Journald,
+}
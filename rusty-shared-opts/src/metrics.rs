@@ -0,0 +1,11 @@
+use clap::Parser;
+
+#[derive(Parser)]
+pub struct Opts {
+    /// Endpoint to serve the Prometheus `/metrics` at. The metrics are not served, if omitted.
+    #[clap(
+        long = "metrics-bind-endpoint",
+        env = "RUSTY_HOME_METRICS_BIND_ENDPOINT"
+    )]
+    pub metrics_bind_endpoint: Option<String>,
+}
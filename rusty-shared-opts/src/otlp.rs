@@ -0,0 +1,9 @@
+use clap::Parser;
+
+#[derive(Parser)]
+pub struct Opts {
+    /// OpenTelemetry collector endpoint, which enables the trace export over OTLP/HTTP.
+    /// For example, `http://localhost:4318/v1/traces`.
+    #[clap(long = "otlp-endpoint", env = "RUSTY_HOME_OTLP_ENDPOINT")]
+    pub otlp_endpoint: Option<String>,
+}
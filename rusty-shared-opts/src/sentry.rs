@@ -1,3 +1,6 @@
+use std::str::FromStr;
+
+use anyhow::{anyhow, bail, Error, Result};
 use clap::Parser;
 
 #[derive(Parser)]
@@ -6,6 +9,22 @@ pub struct Opts {
     #[clap(long = "sentry-dsn", env = "RUSTY_HOME_SENTRY_DSN")]
     pub dsn: Option<String>,
 
+    /// Environment, for example, `production` or `staging`.
+    #[clap(long = "sentry-environment", env = "RUSTY_HOME_SENTRY_ENVIRONMENT")]
+    pub environment: Option<String>,
+
+    /// Server name, defaults to the hostname.
+    #[clap(long = "sentry-server-name", env = "RUSTY_HOME_SENTRY_SERVER_NAME")]
+    pub server_name: Option<String>,
+
+    /// Error event sample rate.
+    #[clap(
+        long = "sentry-sample-rate",
+        env = "RUSTY_HOME_SENTRY_SAMPLE_RATE",
+        default_value = "1.0"
+    )]
+    pub sample_rate: f32,
+
     /// Performance monitoring sample rate.
     #[clap(
         long = "sentry-traces-sample-rate",
@@ -13,4 +32,63 @@ pub struct Opts {
         default_value = "1.0"
     )]
     pub traces_sample_rate: f32,
+
+    /// Sample rate of the spans with the specified name, in addition to the traces sample rate.
+    /// For example, `send=0.01`.
+    #[clap(
+        long = "sentry-span-sample-rate",
+        alias = "sentry-span-sample-rates",
+        env = "RUSTY_HOME_SENTRY_SPAN_SAMPLE_RATES",
+        multiple_occurrences = true,
+        value_delimiter = ','
+    )]
+    pub span_sample_rates: Vec<SpanSampleRate>,
+}
+
+/// Sample rate of the spans with the name.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpanSampleRate {
+    pub name: String,
+    pub rate: f32,
+}
+
+impl FromStr for SpanSampleRate {
+    type Err = Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        let (name, rate) = value
+            .split_once('=')
+            .ok_or_else(|| anyhow!("expected `<span name>=<rate>`, got `{}`", value))?;
+        let rate: f32 = rate.parse()?;
+        if !(0.0..=1.0).contains(&rate) {
+            bail!("the sample rate must be between 0 and 1, got `{}`", rate);
+        }
+        Ok(Self {
+            name: name.to_string(),
+            rate,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_span_sample_rate_ok() -> Result<()> {
+        assert_eq!(
+            "send=0.01".parse::<SpanSampleRate>()?,
+            SpanSampleRate {
+                name: "send".to_string(),
+                rate: 0.01,
+            },
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn parse_span_sample_rate_error() {
+        assert!("send".parse::<SpanSampleRate>().is_err());
+        assert!("send=2".parse::<SpanSampleRate>().is_err());
+    }
 }
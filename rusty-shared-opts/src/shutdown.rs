@@ -0,0 +1,140 @@
+//! Graceful shutdown on `SIGTERM` and `SIGINT`.
+
+use std::future::Future;
+use std::time::Duration;
+
+use anyhow::{bail, Result};
+use async_std::channel::{bounded, Receiver, Sender};
+use clap::Parser;
+use futures::future::{select, Either};
+use futures::pin_mut;
+use tracing::{info, instrument, warn};
+
+use crate::systemd;
+
+#[derive(Parser)]
+pub struct Opts {
+    /// Time, in seconds, given to finish the work in progress after a shutdown signal.
+    #[clap(
+        long = "shutdown-timeout",
+        env = "RUSTY_HOME_SHUTDOWN_TIMEOUT",
+        default_value = "30"
+    )]
+    pub shutdown_timeout_secs: u64,
+}
+
+impl Opts {
+    /// Install the signal handler. It may be only installed once per process.
+    pub fn install(self) -> Result<Shutdown> {
+        let (sender, shutdown) = Shutdown::new(Duration::from_secs(self.shutdown_timeout_secs));
+        ctrlc::set_handler(move || {
+            if sender.close() {
+                warn!("🛑 shutting down…");
+                systemd::notify_stopping();
+            } else {
+                warn!("🛑 already shutting down");
+            }
+        })?;
+        Ok(shutdown)
+    }
+}
+
+/// Shared shutdown signal.
+#[derive(Clone)]
+pub struct Shutdown {
+    /// Gets closed on shutdown, nothing is ever sent.
+    receiver: Receiver<()>,
+
+    timeout: Duration,
+}
+
+impl Shutdown {
+    /// Create the signal, which gets triggered by closing the sender.
+    fn new(timeout: Duration) -> (Sender<()>, Self) {
+        let (sender, receiver) = bounded(1);
+        (sender, Self { receiver, timeout })
+    }
+
+    pub const fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    pub fn is_requested(&self) -> bool {
+        self.receiver.is_closed()
+    }
+
+    /// Wait until the shutdown is requested.
+    pub async fn requested(&self) {
+        let _ = self.receiver.recv().await;
+    }
+
+    /// Run the future, unless the shutdown is requested meanwhile.
+    ///
+    /// The future gets dropped on shutdown, so it must be safe to cancel.
+    pub async fn until<F: Future>(&self, future: F) -> Option<F::Output> {
+        unless(future, self.requested()).await
+    }
+
+    /// Sleep, unless the shutdown is requested meanwhile.
+    ///
+    /// Returns `false` on shutdown.
+    pub async fn sleep(&self, duration: Duration) -> bool {
+        self.until(async_std::task::sleep(duration)).await.is_some()
+    }
+
+    /// Run the service future, which is expected to return on shutdown,
+    /// but at most for the timeout after the shutdown is requested.
+    #[instrument(skip_all)]
+    pub async fn run<T, F: Future<Output = Result<T>>>(&self, future: F) -> Result<T> {
+        let deadline = async {
+            self.requested().await;
+            async_std::task::sleep(self.timeout).await;
+        };
+        match unless(future, deadline).await {
+            Some(result) => {
+                if self.is_requested() {
+                    info!("👋 gracefully shut down");
+                }
+                result
+            }
+            None => bail!("the graceful shutdown has timed out after {:?}", self.timeout),
+        }
+    }
+}
+
+/// Run the future, unless the signal completes first.
+async fn unless<F: Future>(future: F, signal: impl Future<Output = ()>) -> Option<F::Output> {
+    pin_mut!(future, signal);
+    match select(future, signal).await {
+        Either::Left((output, _)) => Some(output),
+        Either::Right(_) => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[async_std::test]
+    async fn until_ok() {
+        let (sender, shutdown) = Shutdown::new(Duration::from_secs(1));
+        assert_eq!(shutdown.until(async { 42 }).await, Some(42));
+        assert!(!shutdown.is_requested());
+
+        sender.close();
+        assert!(shutdown.is_requested());
+        assert_eq!(shutdown.until(futures::future::pending::<()>()).await, None);
+        assert!(!shutdown.sleep(Duration::from_secs(60)).await);
+    }
+
+    #[async_std::test]
+    async fn run_timeout_error() {
+        let (sender, shutdown) = Shutdown::new(Duration::from_millis(10));
+        sender.close();
+        assert!(shutdown.run(async { Ok(42) }).await.is_ok());
+        assert!(shutdown
+            .run(futures::future::pending::<Result<()>>())
+            .await
+            .is_err());
+    }
+}
@@ -0,0 +1,33 @@
+//! [`sd_notify`](https://www.freedesktop.org/software/systemd/man/sd_notify.html) integration.
+//!
+//! The notifications are no-op unless the microservice is run by systemd with `Type=notify`.
+
+use sd_notify::NotifyState;
+use tracing::{debug, warn};
+
+/// Tell systemd that the microservice has started up.
+pub fn notify_ready(status: &str) {
+    notify(&[NotifyState::Ready, NotifyState::Status(status)]);
+}
+
+/// Update the human-readable status, which `systemctl status` shows.
+pub fn notify_status(status: &str) {
+    notify(&[NotifyState::Status(status)]);
+}
+
+/// Ping the watchdog, see `WatchdogSec=`.
+pub fn notify_watchdog() {
+    notify(&[NotifyState::Watchdog]);
+}
+
+/// Tell systemd that the microservice is shutting down.
+pub fn notify_stopping() {
+    notify(&[NotifyState::Stopping, NotifyState::Status("shutting down")]);
+}
+
+fn notify(states: &[NotifyState]) {
+    debug!(?states, "notifying systemd…");
+    if let Err(error) = sd_notify::notify(false, states) {
+        warn!("failed to notify systemd: {:#}", error);
+    }
+}
@@ -16,8 +16,20 @@ use async_std::future::timeout;
 use fred::pool::RedisPool;
 use fred::prelude::*;
 use fred::types::{CustomCommand, MultipleKeys, MultipleValues, PerformanceConfig, RedisKey};
+use once_cell::sync::Lazy;
+use rusty_shared_metrics::{register_histogram_vec, HistogramVec};
 use tracing::{debug, instrument};
 
+static SCRIPT_DURATION: Lazy<HistogramVec> = Lazy::new(|| {
+    register_histogram_vec!(
+        "rusty_redis_script_duration_seconds",
+        "Redis script call duration",
+        &["script"],
+    )
+    .unwrap()
+});
+
+#[derive(Clone)]
 pub struct Redis {
     pub pool: RedisPool,
     script_hashes: ScriptHashes,
@@ -27,6 +39,11 @@ pub struct Redis {
 struct ScriptHashes {
     set_if_greater: String,
     set_if_not_equal: String,
+    delete_if_equal: String,
+    hset_with_ttl: String,
+    hset_if_equal: String,
+    set_nx_if_older: String,
+    acquire_lease: String,
     create_consumer_group: String,
 }
 
@@ -50,8 +67,11 @@ impl Redis {
 
         let pool = RedisPool::new(config, 2)?;
         connect(&pool).await?;
-        pool.custom(CustomCommand::new_static("CLIENT SETNAME", None, true), vec![client_name])
-            .await?;
+        pool.custom::<(), _>(
+            CustomCommand::new_static("CLIENT SETNAME", None, true),
+            vec![client_name],
+        )
+        .await?;
         let script_hashes = load_scripts(&pool).await?;
 
         Ok(Self {
@@ -66,6 +86,9 @@ impl Redis {
         key: K,
         group_name: &str,
     ) -> Result<bool> {
+        let _timer = SCRIPT_DURATION
+            .with_label_values(&["create_consumer_group"])
+            .start_timer();
         timeout(
             Self::EVALSHA_TIMEOUT,
             self.pool
@@ -84,6 +107,9 @@ impl Redis {
         V: 'static + TryInto<MultipleValues>,
         V::Error: Into<RedisError>,
     {
+        let _timer = SCRIPT_DURATION
+            .with_label_values(&["set_if_greater"])
+            .start_timer();
         timeout(
             Self::EVALSHA_TIMEOUT,
             self.pool
@@ -102,6 +128,9 @@ impl Redis {
         V: 'static + TryInto<MultipleValues>,
         V::Error: Into<RedisError>,
     {
+        let _timer = SCRIPT_DURATION
+            .with_label_values(&["set_if_not_equal"])
+            .start_timer();
         timeout(
             Self::EVALSHA_TIMEOUT,
             self.pool
@@ -111,6 +140,142 @@ impl Redis {
         .context("timed out while calling set-if-not-equal")?
         .context("failed to set-if-not-equal")
     }
+
+    /// Delete the key, if it still holds the specified value.
+    ///
+    /// Returns whether the key has been deleted.
+    #[instrument(skip_all, fields(key = ?key))]
+    pub async fn delete_if_equal<K, V>(&self, key: K, value: V) -> Result<bool>
+    where
+        K: Debug + Into<MultipleKeys>,
+        V: TryInto<MultipleValues>,
+        V::Error: Into<RedisError>,
+    {
+        let _timer = SCRIPT_DURATION
+            .with_label_values(&["delete_if_equal"])
+            .start_timer();
+        timeout(
+            Self::EVALSHA_TIMEOUT,
+            self.pool
+                .evalsha(&self.script_hashes.delete_if_equal, key, value),
+        )
+        .await
+        .context("timed out while calling delete-if-equal")?
+        .context("failed to delete-if-equal")
+    }
+
+    /// Set the hash fields and the key TTL at once.
+    #[instrument(skip_all, fields(key = ?key))]
+    pub async fn hset_with_ttl<K>(
+        &self,
+        key: K,
+        fields: Vec<(String, String)>,
+        ttl_secs: u64,
+    ) -> Result<()>
+    where
+        K: Debug + Into<MultipleKeys>,
+    {
+        let _timer = SCRIPT_DURATION
+            .with_label_values(&["hset_with_ttl"])
+            .start_timer();
+        let args: Vec<String> = std::iter::once(ttl_secs.to_string())
+            .chain(fields.into_iter().flat_map(|(field, value)| [field, value]))
+            .collect();
+        timeout(
+            Self::EVALSHA_TIMEOUT,
+            self.pool
+                .evalsha(&self.script_hashes.hset_with_ttl, key, args),
+        )
+        .await
+        .context("timed out while calling hset-with-ttl")?
+        .context("failed to hset-with-ttl")
+    }
+
+    /// Set the hash fields, if all of them still hold the expected values.
+    ///
+    /// The fields are `(field, expected value, new value)`, where `None` means a missing field.
+    /// Returns whether the fields have been set.
+    #[instrument(skip_all, fields(key = ?key))]
+    pub async fn hset_if_equal<K>(
+        &self,
+        key: K,
+        fields: Vec<(String, Option<String>, String)>,
+    ) -> Result<bool>
+    where
+        K: Debug + Into<MultipleKeys>,
+    {
+        let _timer = SCRIPT_DURATION
+            .with_label_values(&["hset_if_equal"])
+            .start_timer();
+        let args: Vec<String> = fields
+            .into_iter()
+            .flat_map(|(field, expected, value)| [field, expected.unwrap_or_default(), value])
+            .collect();
+        timeout(
+            Self::EVALSHA_TIMEOUT,
+            self.pool
+                .evalsha(&self.script_hashes.hset_if_equal, key, args),
+        )
+        .await
+        .context("timed out while calling hset-if-equal")?
+        .context("failed to hset-if-equal")
+    }
+
+    /// Copy the Unix time from `timestamp_key` into `key`, unless `key` exists,
+    /// if the time is not after `max_timestamp`.
+    ///
+    /// The check and the set are atomic, so that a concurrent update of `timestamp_key`
+    /// either happens before the check, or sees `key` set.
+    /// Returns the copied time, if `key` has been set.
+    #[instrument(skip_all, fields(key = ?key, timestamp_key = ?timestamp_key))]
+    pub async fn set_nx_if_older<K>(
+        &self,
+        key: K,
+        timestamp_key: K,
+        max_timestamp: i64,
+    ) -> Result<Option<i64>>
+    where
+        K: Debug + Into<RedisKey>,
+    {
+        let _timer = SCRIPT_DURATION
+            .with_label_values(&["set_nx_if_older"])
+            .start_timer();
+        timeout(
+            Self::EVALSHA_TIMEOUT,
+            self.pool.evalsha(
+                &self.script_hashes.set_nx_if_older,
+                vec![key.into(), timestamp_key.into()],
+                max_timestamp,
+            ),
+        )
+        .await
+        .context("timed out while calling set-nx-if-older")?
+        .context("failed to set-nx-if-older")
+    }
+
+    /// Take the lease for the holder, or extend it if the holder already has it.
+    ///
+    /// Returns whether the lease is held by the holder. Release it with [`Self::delete_if_equal`].
+    #[instrument(skip_all, fields(key = ?key, holder = holder))]
+    pub async fn acquire_lease<K>(&self, key: K, holder: &str, ttl: time::Duration) -> Result<bool>
+    where
+        K: Debug + Into<MultipleKeys>,
+    {
+        let _timer = SCRIPT_DURATION
+            .with_label_values(&["acquire_lease"])
+            .start_timer();
+        timeout(
+            Self::EVALSHA_TIMEOUT,
+            self.pool.evalsha(
+                &self.script_hashes.acquire_lease,
+                key,
+                vec![holder.to_string(), ttl.as_millis().to_string()],
+            ),
+        )
+        .await
+        .context("timed out while calling acquire-lease")?
+        .context("failed to acquire-lease")
+    }
 }
 
 #[instrument(skip_all)]
@@ -130,11 +295,21 @@ async fn load_scripts(client: &RedisPool) -> Result<ScriptHashes> {
     let set_if_greater = client.script_load(SET_IF_GREATER_SCRIPT).await?;
     let create_consumer_group = client.script_load(CREATE_CONSUMER_GROUP).await?;
     let set_if_not_equal = client.script_load(SET_IF_NOT_EQUAL_SCRIPT).await?;
+    let delete_if_equal = client.script_load(DELETE_IF_EQUAL_SCRIPT).await?;
+    let hset_with_ttl = client.script_load(HSET_WITH_TTL_SCRIPT).await?;
+    let hset_if_equal = client.script_load(HSET_IF_EQUAL_SCRIPT).await?;
+    let set_nx_if_older = client.script_load(SET_NX_IF_OLDER_SCRIPT).await?;
+    let acquire_lease = client.script_load(ACQUIRE_LEASE_SCRIPT).await?;
 
     let hashes = ScriptHashes {
         set_if_greater,
         create_consumer_group,
         set_if_not_equal,
+        delete_if_equal,
+        hset_with_ttl,
+        hset_if_equal,
+        set_nx_if_older,
+        acquire_lease,
     };
 
     debug!(hashes = ?hashes, "loaded the scripts");
@@ -168,6 +343,70 @@ const SET_IF_NOT_EQUAL_SCRIPT: &str = r#"
     end
 "#;
 
+/// Delete the key, if it holds the specified value.
+// language=lua
+const DELETE_IF_EQUAL_SCRIPT: &str = r#"
+    if redis.call("GET", KEYS[1]) == ARGV[1] then
+        return redis.call("DEL", KEYS[1])
+    else
+        return 0
+    end
+"#;
+
+/// Set the hash fields from `ARGV[2..]`, and expire the hash in `ARGV[1]` seconds.
+// language=lua
+const HSET_WITH_TTL_SCRIPT: &str = r#"
+    redis.call("HSET", KEYS[1], unpack(ARGV, 2));
+    redis.call("EXPIRE", KEYS[1], ARGV[1]);
+"#;
+
+/// Set the hash fields, if all of them hold the expected values.
+/// `ARGV` consists of the `field, expected value, new value` triples,
+/// where an empty expected value stands for a missing field.
+// language=lua
+const HSET_IF_EQUAL_SCRIPT: &str = r#"
+    for i = 1, #ARGV, 3 do
+        if (redis.call("HGET", KEYS[1], ARGV[i]) or "") ~= ARGV[i + 1] then
+            return 0
+        end
+    end
+
+    for i = 1, #ARGV, 3 do
+        redis.call("HSET", KEYS[1], ARGV[i], ARGV[i + 2]);
+    end
+    return 1
+"#;
+
+/// Copy the Unix time from `KEYS[2]` into `KEYS[1]`, unless the latter exists,
+/// if the time is not after `ARGV[1]`.
+// language=lua
+const SET_NX_IF_OLDER_SCRIPT: &str = r#"
+    local timestamp = tonumber(redis.call("GET", KEYS[2]));
+
+    if timestamp == nil or timestamp > tonumber(ARGV[1]) then
+        return false
+    end
+    if redis.call("SET", KEYS[1], timestamp, "NX") then
+        return timestamp
+    else
+        return false
+    end
+"#;
+
+/// Set the key to the holder `ARGV[1]` with the TTL of `ARGV[2]` milliseconds, unless it's set.
+/// If the key already holds the holder, only extend its TTL.
+// language=lua
+const ACQUIRE_LEASE_SCRIPT: &str = r#"
+    if redis.call("SET", KEYS[1], ARGV[1], "NX", "PX", ARGV[2]) then
+        return 1
+    end
+    if redis.call("GET", KEYS[1]) == ARGV[1] then
+        redis.call("PEXPIRE", KEYS[1], ARGV[2]);
+        return 1
+    end
+    return 0
+"#;
+
 /// Create a consumer group, if not exists.
 // language=lua
 const CREATE_CONSUMER_GROUP: &str = r#"
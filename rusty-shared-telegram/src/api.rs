@@ -1,7 +1,10 @@
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::Arc;
 use std::time;
 
 use anyhow::Result;
 use reqwest::Client;
+use rusty_shared_time::now;
 use tracing::instrument;
 
 const USER_AGENT: &str = concat!(
@@ -14,19 +17,46 @@ const USER_AGENT: &str = concat!(
 pub struct BotApi {
     pub(crate) client: Client,
     pub(crate) base_url: String,
+
+    /// Unix time of the most recent successful call, zero until there's one.
+    last_success_at: Arc<AtomicI64>,
 }
 
 impl BotApi {
-    #[instrument(level = "debug", skip_all)]
-    pub fn new(token: String, timeout: time::Duration) -> Result<Self> {
+    /// The official Bot API server.
+    pub const DEFAULT_BASE_URL: &'static str = "https://api.telegram.org";
+
+    /// Create the Bot API client.
+    ///
+    /// `base_url` allows pointing the client to a [self-hosted](https://github.com/tdlib/telegram-bot-api)
+    /// Bot API server.
+    #[instrument(level = "debug", skip_all, fields(base_url = base_url))]
+    pub fn new(base_url: &str, token: &str, timeout: time::Duration) -> Result<Self> {
         let client = Client::builder()
             .user_agent(USER_AGENT)
             .timeout(timeout)
             .build()?;
         let this = Self {
             client,
-            base_url: format!("https://api.telegram.org/bot{}", token),
+            base_url: format!("{}/bot{}", base_url.trim_end_matches('/'), token),
+            last_success_at: Arc::default(),
         };
         Ok(this)
     }
+
+    /// Unix time of the most recent successful call, if any.
+    pub fn last_success_at(&self) -> Option<i64> {
+        Some(self.last_success_at.load(Ordering::Relaxed)).filter(|timestamp| *timestamp != 0)
+    }
+
+    pub(crate) fn on_success(&self) {
+        self.last_success_at.store(now(), Ordering::Relaxed);
+    }
+}
+
+/// Allows using the client itself as a command handler context.
+impl AsRef<BotApi> for BotApi {
+    fn as_ref(&self) -> &BotApi {
+        self
+    }
 }
@@ -0,0 +1,403 @@
+//! Bot command router.
+//!
+//! Parses `bot_command` [entities](https://core.telegram.org/bots/api#messageentity),
+//! dispatches them to the registered handlers, and produces the matching
+//! [`SetMyCommands`](methods::SetMyCommands) call.
+//!
+//! The router also dispatches the inline keyboard [callback queries](models::CallbackQuery)
+//! by their callback data.
+
+use std::borrow::Cow;
+use std::future::Future;
+
+use anyhow::{Context, Error, Result};
+use futures::future::BoxFuture;
+use tracing::{debug, info, instrument, warn};
+
+use crate::api::BotApi;
+use crate::methods::Method;
+use crate::{methods, models};
+
+/// Parsed bot command invocation.
+#[derive(Debug, PartialEq, Eq)]
+pub struct ParsedCommand<'a> {
+    /// Command name without the leading slash.
+    pub name: &'a str,
+
+    /// Bot username, if the command is addressed as `/command@username`.
+    pub username: Option<&'a str>,
+
+    /// The rest of the message text, trimmed.
+    pub args: &'a str,
+}
+
+impl<'a> ParsedCommand<'a> {
+    /// Parse the command, which must be the message's leading `bot_command` entity.
+    pub fn from_message(message: &'a models::Message) -> Option<Self> {
+        let text = message.text.as_deref()?;
+        let entity = message.entities.iter().find(|entity| {
+            entity.kind == models::MessageEntityKind::BotCommand && entity.offset == 0
+        })?;
+        let end = utf16_to_byte_offset(text, entity.length)?;
+        let command = text[..end].strip_prefix('/')?;
+        let (name, username) = match command.split_once('@') {
+            Some((name, username)) => (name, Some(username)),
+            None => (command, None),
+        };
+        Some(Self {
+            name,
+            username,
+            args: text[end..].trim(),
+        })
+    }
+
+    /// Check whether the command is addressed to the specified bot.
+    pub const fn is_addressed_to(&self, bot_username: Option<&str>) -> bool {
+        match (self.username, bot_username) {
+            (None, _) => true,
+            (Some(username), Some(bot_username)) => username.eq_ignore_ascii_case(bot_username),
+            (Some(_), None) => false,
+        }
+    }
+}
+
+/// Convert the offset in UTF-16 code units into the byte offset.
+fn utf16_to_byte_offset(text: &str, utf16_offset: usize) -> Option<usize> {
+    let mut n_code_units = 0;
+    for (byte_offset, char_) in text.char_indices() {
+        if n_code_units == utf16_offset {
+            return Some(byte_offset);
+        }
+        n_code_units += char_.len_utf16();
+    }
+    (n_code_units == utf16_offset).then_some(text.len())
+}
+
+/// Typed command arguments.
+pub trait FromArgs: Sized {
+    fn from_args(args: &str) -> Result<Self>;
+}
+
+/// Ignores any arguments.
+impl FromArgs for () {
+    fn from_args(_args: &str) -> Result<Self> {
+        Ok(())
+    }
+}
+
+/// The entire argument string.
+impl FromArgs for String {
+    fn from_args(args: &str) -> Result<Self> {
+        Ok(args.to_string())
+    }
+}
+
+/// Whitespace-separated arguments.
+impl FromArgs for Vec<String> {
+    fn from_args(args: &str) -> Result<Self> {
+        Ok(args.split_whitespace().map(ToString::to_string).collect())
+    }
+}
+
+/// `None` when there are no arguments, otherwise the entire argument string parsed as `T`.
+impl<T: FromArgs> FromArgs for Option<T> {
+    fn from_args(args: &str) -> Result<Self> {
+        if args.is_empty() {
+            Ok(None)
+        } else {
+            T::from_args(args).map(Some)
+        }
+    }
+}
+
+macro_rules! impl_from_args_via_from_str {
+    ($($type_:ty),*) => {
+        $(
+            impl FromArgs for $type_ {
+                fn from_args(args: &str) -> Result<Self> {
+                    args.parse().with_context(|| format!("`{}` is not a valid number", args))
+                }
+            }
+        )*
+    };
+}
+
+impl_from_args_via_from_str!(i64, u64, i32, u32, u16, u8, f64);
+
+/// Replies to the commands with invalid arguments.
+///
+/// The context renders the reply, so that it can be localized like any other reply.
+pub trait ReplyInvalidArgs {
+    fn reply_invalid_args(
+        self,
+        message: models::Message,
+        command: &'static str,
+        error: Error,
+    ) -> BoxFuture<'static, Result<()>>;
+}
+
+type BoxedHandler<C> =
+    Box<dyn Fn(C, models::Message, &str) -> BoxFuture<'static, Result<()>> + Send + Sync>;
+
+type BoxedCallbackHandler<C> =
+    Box<dyn Fn(C, models::CallbackQuery) -> BoxFuture<'static, Result<()>> + Send + Sync>;
+
+struct Route<C> {
+    command: &'static str,
+    description: &'static str,
+    handler: BoxedHandler<C>,
+}
+
+struct CallbackRoute<C> {
+    data: &'static str,
+    handler: BoxedCallbackHandler<C>,
+}
+
+/// Dispatches bot commands to their handlers.
+///
+/// `C` is the context which gets passed to each handler call.
+pub struct CommandRouter<C> {
+    /// The bot's own username, see [`methods::GetMe`].
+    bot_username: Option<String>,
+
+    routes: Vec<Route<C>>,
+
+    callback_routes: Vec<CallbackRoute<C>>,
+}
+
+impl<C> CommandRouter<C> {
+    pub const fn new(bot_username: Option<String>) -> Self {
+        Self {
+            bot_username,
+            routes: Vec::new(),
+            callback_routes: Vec::new(),
+        }
+    }
+
+    /// Build the [`methods::SetMyCommands`] call from the registered commands.
+    pub fn set_my_commands(&self) -> methods::SetMyCommands {
+        self.routes
+            .iter()
+            .fold(methods::SetMyCommands::default(), |method, route| {
+                method.command(models::BotCommand {
+                    command: Cow::Borrowed(route.command),
+                    description: Cow::Borrowed(route.description),
+                })
+            })
+    }
+
+    /// Dispatch the message to the matching handler.
+    ///
+    /// Returns `false` when the message is not a command, is addressed to another bot,
+    /// or the command is unknown.
+    #[instrument(skip_all, fields(message.id = message.id))]
+    pub async fn dispatch(&self, context: C, message: models::Message) -> Result<bool> {
+        let (route, args) = match ParsedCommand::from_message(&message) {
+            Some(command) if !command.is_addressed_to(self.bot_username.as_deref()) => {
+                debug!(?command.username, "the command is addressed to another bot");
+                return Ok(false);
+            }
+            Some(command) => {
+                match self
+                    .routes
+                    .iter()
+                    .find(|route| route.command == command.name)
+                {
+                    Some(route) => (route, command.args.to_string()),
+                    None => {
+                        debug!(command.name, "unknown command");
+                        return Ok(false);
+                    }
+                }
+            }
+            None => {
+                return Ok(false);
+            }
+        };
+        info!(command = route.command, "dispatching…");
+        (route.handler)(context, message, &args)
+            .await
+            .with_context(|| format!("`/{}` failed", route.command))?;
+        Ok(true)
+    }
+}
+
+impl<C: ReplyInvalidArgs + Send + 'static> CommandRouter<C> {
+    /// Register the command handler.
+    ///
+    /// The handler receives the context, the original message and the parsed arguments.
+    /// When the arguments are invalid, the context replies with the parsing error instead,
+    /// see [`ReplyInvalidArgs`].
+    pub fn command<A, F, Fut>(
+        mut self,
+        command: &'static str,
+        description: &'static str,
+        handler: F,
+    ) -> Self
+    where
+        A: FromArgs,
+        F: Fn(C, models::Message, A) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<()>> + Send + 'static,
+    {
+        let handler: BoxedHandler<C> =
+            Box::new(move |context, message, args| match A::from_args(args) {
+                Ok(args) => Box::pin(handler(context, message, args)),
+                Err(error) => {
+                    warn!(command, "invalid arguments: {:#}", error);
+                    context.reply_invalid_args(message, command, error)
+                }
+            });
+        self.routes.push(Route {
+            command,
+            description,
+            handler,
+        });
+        self
+    }
+}
+
+impl<C: AsRef<BotApi>> CommandRouter<C> {
+    /// Register the handler of the callback queries with the specified callback data.
+    pub fn callback<F, Fut>(mut self, data: &'static str, handler: F) -> Self
+    where
+        F: Fn(C, models::CallbackQuery) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<()>> + Send + 'static,
+    {
+        let handler: BoxedCallbackHandler<C> =
+            Box::new(move |context, query| Box::pin(handler(context, query)));
+        self.callback_routes.push(CallbackRoute { data, handler });
+        self
+    }
+
+    /// Answer the callback query and dispatch it to the matching handler.
+    ///
+    /// The query gets answered even when its data is unknown,
+    /// so that the client stops showing the progress bar.
+    /// Returns `false` when there is no matching handler.
+    #[instrument(skip_all, fields(callback_query.id = query.id, callback_query.data = query.data))]
+    pub async fn dispatch_callback(
+        &self,
+        context: C,
+        query: models::CallbackQuery,
+    ) -> Result<bool> {
+        methods::AnswerCallbackQuery::new(&query.id)
+            .call(context.as_ref())
+            .await?;
+        let route = match self
+            .callback_routes
+            .iter()
+            .find(|route| query.data.as_deref() == Some(route.data))
+        {
+            Some(route) => route,
+            None => {
+                debug!("unknown callback data");
+                return Ok(false);
+            }
+        };
+        info!(data = route.data, "dispatching the callback query…");
+        (route.handler)(context, query)
+            .await
+            .with_context(|| format!("`{}` callback failed", route.data))?;
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::from_str;
+
+    use super::*;
+
+    fn message(json: &str) -> Result<models::Message> {
+        Ok(from_str(json)?)
+    }
+
+    #[test]
+    fn parse_command_ok() -> Result<()> {
+        let message = message(
+            // language=json
+            r#"{"message_id":1,"chat":{"id":1},"text":"/start foo bar ","entities":[{"type":"bot_command","offset":0,"length":6}]}"#,
+        )?;
+        assert_eq!(
+            ParsedCommand::from_message(&message),
+            Some(ParsedCommand {
+                name: "start",
+                username: None,
+                args: "foo bar",
+            })
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn parse_command_with_username_ok() -> Result<()> {
+        let message = message(
+            // language=json
+            r#"{"message_id":1,"chat":{"id":1},"text":"/start@RustyBot","entities":[{"type":"bot_command","offset":0,"length":15}]}"#,
+        )?;
+        let command = ParsedCommand::from_message(&message).context("not a command")?;
+        assert_eq!(command.name, "start");
+        assert_eq!(command.username, Some("RustyBot"));
+        assert!(command.is_addressed_to(Some("rustybot")));
+        assert!(!command.is_addressed_to(Some("OtherBot")));
+        Ok(())
+    }
+
+    #[test]
+    fn parse_command_prefix_ok() -> Result<()> {
+        let message = message(
+            // language=json
+            r#"{"message_id":1,"chat":{"id":1},"text":"/startle","entities":[{"type":"bot_command","offset":0,"length":8}]}"#,
+        )?;
+        assert_eq!(
+            ParsedCommand::from_message(&message)
+                .context("not a command")?
+                .name,
+            "startle"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn parse_command_utf16_args_ok() -> Result<()> {
+        let message = message(
+            // language=json
+            r#"{"message_id":1,"chat":{"id":1},"text":"/zone 🏡 home","entities":[{"type":"bot_command","offset":0,"length":5}]}"#,
+        )?;
+        assert_eq!(
+            ParsedCommand::from_message(&message)
+                .context("not a command")?
+                .args,
+            "🏡 home"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn not_a_command_ok() -> Result<()> {
+        let message = message(
+            // language=json
+            r#"{"message_id":1,"chat":{"id":1},"text":"hello /start","entities":[{"type":"bot_command","offset":6,"length":6}]}"#,
+        )?;
+        assert_eq!(ParsedCommand::from_message(&message), None);
+        Ok(())
+    }
+
+    #[test]
+    fn utf16_to_byte_offset_ok() {
+        assert_eq!(utf16_to_byte_offset("🏡 home", 0), Some(0));
+        assert_eq!(utf16_to_byte_offset("🏡 home", 2), Some(4));
+        assert_eq!(utf16_to_byte_offset("🏡 home", 7), Some(9));
+        assert_eq!(utf16_to_byte_offset("🏡 home", 1), None);
+        assert_eq!(utf16_to_byte_offset("🏡 home", 8), None);
+    }
+
+    #[test]
+    fn from_args_ok() -> Result<()> {
+        assert_eq!(Option::<u8>::from_args("")?, None);
+        assert_eq!(Option::<u8>::from_args("42")?, Some(42));
+        assert_eq!(Vec::<String>::from_args(" a  b ")?, vec!["a", "b"]);
+        assert!(u8::from_args("foo").is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,139 @@
+//! Safe message formatting for the [`ParseMode`]s.
+//!
+//! See: https://core.telegram.org/bots/api#formatting-options.
+
+use std::borrow::Cow;
+use std::fmt::Display;
+
+use crate::models::ParseMode;
+
+impl ParseMode {
+    /// Escape the arbitrary text, so that it's rendered literally.
+    pub fn escape<'a>(self, text: &'a str) -> Cow<'a, str> {
+        match self {
+            Self::MarkdownV2 => escape_with(text, |char_| match char_ {
+                '_' | '*' | '[' | ']' | '(' | ')' | '~' | '`' | '>' | '#' | '+' | '-' | '='
+                | '|' | '{' | '}' | '.' | '!' | '\\' => Some("\\"),
+                _ => None,
+            }),
+            Self::Html => escape_html(text),
+        }
+    }
+
+    /// Render the value in bold.
+    pub fn bold(self, value: impl Display) -> String {
+        let value = value.to_string();
+        match self {
+            Self::MarkdownV2 => format!("*{}*", self.escape(&value)),
+            Self::Html => format!("<b>{}</b>", self.escape(&value)),
+        }
+    }
+
+    /// Render the value in italic.
+    pub fn italic(self, value: impl Display) -> String {
+        let value = value.to_string();
+        match self {
+            Self::MarkdownV2 => format!("_{}_", self.escape(&value)),
+            Self::Html => format!("<i>{}</i>", self.escape(&value)),
+        }
+    }
+
+    /// Render the value as inline fixed-width code.
+    pub fn code(self, value: impl Display) -> String {
+        let value = value.to_string();
+        match self {
+            Self::MarkdownV2 => format!("`{}`", escape_markdown_v2_code(&value)),
+            Self::Html => format!("<code>{}</code>", escape_html(&value)),
+        }
+    }
+
+    /// Render the inline link.
+    pub fn link(self, text: impl Display, url: &str) -> String {
+        let text = text.to_string();
+        match self {
+            Self::MarkdownV2 => {
+                let url = escape_with(url, |char_| matches!(char_, ')' | '\\').then_some("\\"));
+                format!("[{}]({})", self.escape(&text), url)
+            }
+            Self::Html => format!(r#"<a href="{}">{}</a>"#, escape_html(url), escape_html(&text)),
+        }
+    }
+}
+
+/// Inside `pre` and `code` entities, only `` ` `` and `\` must be escaped.
+fn escape_markdown_v2_code(text: &str) -> Cow<'_, str> {
+    escape_with(text, |char_| matches!(char_, '`' | '\\').then_some("\\"))
+}
+
+fn escape_html(text: &str) -> Cow<'_, str> {
+    let mut escaped = String::with_capacity(text.len());
+    for char_ in text.chars() {
+        match char_ {
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '&' => escaped.push_str("&amp;"),
+            '"' => escaped.push_str("&quot;"),
+            _ => escaped.push(char_),
+        }
+    }
+    if escaped.len() == text.len() {
+        Cow::Borrowed(text)
+    } else {
+        Cow::Owned(escaped)
+    }
+}
+
+/// Prepend the prefix returned by `prefix_of` to each matching character.
+fn escape_with(text: &str, prefix_of: impl Fn(char) -> Option<&'static str>) -> Cow<'_, str> {
+    if !text.chars().any(|char_| prefix_of(char_).is_some()) {
+        return Cow::Borrowed(text);
+    }
+    let mut escaped = String::with_capacity(text.len() * 2);
+    for char_ in text.chars() {
+        if let Some(prefix) = prefix_of(char_) {
+            escaped.push_str(prefix);
+        }
+        escaped.push(char_);
+    }
+    Cow::Owned(escaped)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escape_markdown_v2_ok() {
+        assert_eq!(ParseMode::MarkdownV2.escape("42"), "42");
+        assert_eq!(ParseMode::MarkdownV2.escape("-1.5 (ok)!"), r#"\-1\.5 \(ok\)\!"#);
+        assert_eq!(ParseMode::MarkdownV2.escape(r#"a\b"#), r#"a\\b"#);
+    }
+
+    #[test]
+    fn escape_html_ok() {
+        assert_eq!(ParseMode::Html.escape("-1.5!"), "-1.5!");
+        assert_eq!(ParseMode::Html.escape("<b>&</b>"), "&lt;b&gt;&amp;&lt;/b&gt;");
+    }
+
+    #[test]
+    fn helpers_markdown_v2_ok() {
+        assert_eq!(ParseMode::MarkdownV2.bold("95%"), "*95%*");
+        assert_eq!(ParseMode::MarkdownV2.bold(-1.5), r#"*\-1\.5*"#);
+        assert_eq!(ParseMode::MarkdownV2.code("-100.5"), "`-100.5`");
+        assert_eq!(ParseMode::MarkdownV2.code("a`b"), r#"`a\`b`"#);
+        assert_eq!(
+            ParseMode::MarkdownV2.link("map.", "https://example.com/(1)"),
+            r#"[map\.](https://example.com/(1\))"#,
+        );
+    }
+
+    #[test]
+    fn helpers_html_ok() {
+        assert_eq!(ParseMode::Html.bold("a<b"), "<b>a&lt;b</b>");
+        assert_eq!(ParseMode::Html.code(42), "<code>42</code>");
+        assert_eq!(
+            ParseMode::Html.link("map", r#"https://example.com/?a=1&b="2""#),
+            r#"<a href="https://example.com/?a=1&amp;b=&quot;2&quot;">map</a>"#,
+        );
+    }
+}
@@ -9,6 +9,10 @@
 )]
 
 pub mod api;
+pub mod commands;
+pub mod formatting;
 pub mod headers;
 pub mod methods;
+#[cfg(any(test, feature = "mock"))]
+pub mod mock;
 pub mod models;
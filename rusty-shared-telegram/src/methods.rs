@@ -1,16 +1,30 @@
 use std::fmt::Debug;
 use std::time;
 
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use async_trait::async_trait;
+use once_cell::sync::Lazy;
+use reqwest::multipart::{Form, Part};
+use rusty_shared_metrics::{register_int_counter_vec, IntCounterVec};
 use serde::de::DeserializeOwned;
 use serde::Serialize;
+use serde_json::Value;
 use serde_with::{serde_as, DurationSeconds};
 use tracing::{debug, info, instrument};
 
 use crate::api::BotApi;
 use crate::models;
 
+/// Outcome is `ok`, `api_error` when Telegram has refused the call, or `error` otherwise.
+static CALLS: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "rusty_telegram_calls_total",
+        "Telegram Bot API calls by method and outcome",
+        &["method", "outcome"],
+    )
+    .unwrap()
+});
+
 #[async_trait]
 pub trait Method: Debug + Sized + Serialize {
     type Output: DeserializeOwned;
@@ -18,25 +32,87 @@ pub trait Method: Debug + Sized + Serialize {
     /// The method's name in Telegram Bot API.
     const NAME: &'static str;
 
+    /// Files to upload along with the call.
+    ///
+    /// The call is sent as `multipart/form-data` when there are any, and as JSON otherwise.
+    fn files(&self) -> Vec<(&'static str, &models::InputFile)> {
+        Vec::new()
+    }
+
     /// Call the method on the specified connection.
     #[instrument(skip_all, fields(name = Self::NAME))]
     async fn call(&self, api: &BotApi) -> Result<Self::Output> {
         info!("calling…");
         debug!(self = ?self);
-        let text = api
-            .client
-            .post(format!("{}/{}", api.base_url, Self::NAME))
-            .json(self)
-            .send()
-            .await
-            .with_context(|| format!("failed to send the `{}` request", Self::NAME))?
-            .text_with_charset("utf-8")
-            .await?;
+        let request = api.client.post(format!("{}/{}", api.base_url, Self::NAME));
+        let files = self.files();
+        let request = if files.is_empty() {
+            request.json(self)
+        } else {
+            request.multipart(self.to_form(files)?)
+        };
+        let response = async {
+            let text = request
+                .send()
+                .await
+                .with_context(|| format!("failed to send the `{}` request", Self::NAME))?
+                .text_with_charset("utf-8")
+                .await?;
+            debug!(response.text = ?text, "completed the request");
+            serde_json::from_str::<models::Response<Self::Output>>(&text)
+                .with_context(|| format!("failed to deserialize `{}` response", Self::NAME))
+        };
+        let result: Result<Self::Output> = match response.await {
+            Ok(response) => response.into(),
+            Err(error) => {
+                CALLS.with_label_values(&[Self::NAME, "error"]).inc();
+                return Err(error);
+            }
+        };
+        if result.is_ok() {
+            CALLS.with_label_values(&[Self::NAME, "ok"]).inc();
+            api.on_success();
+        } else {
+            CALLS.with_label_values(&[Self::NAME, "api_error"]).inc();
+        }
+        result
+    }
+
+    /// Build the `multipart/form-data` body from the parameters and the files.
+    fn to_form(&self, files: Vec<(&'static str, &models::InputFile)>) -> Result<Form> {
+        let mut form = Form::new();
+        for (name, value) in self.form_fields()? {
+            form = form.text(name, value);
+        }
+        for (name, file) in files {
+            let mut part = Part::bytes(file.content.clone()).file_name(file.file_name.clone());
+            if let Some(mime_type) = &file.mime_type {
+                part = part
+                    .mime_str(mime_type)
+                    .with_context(|| format!("invalid MIME type `{}`", mime_type))?;
+            }
+            form = form.part(name, part);
+        }
+        Ok(form)
+    }
 
-        debug!(response.text = ?text, "completed the request");
-        serde_json::from_str::<models::Response<Self::Output>>(&text)
-            .with_context(|| format!("failed to deserialize `{}` response", Self::NAME))?
-            .into()
+    /// Serialize the parameters into the text form fields.
+    ///
+    /// Strings are passed as is, and other values are JSON-serialized as the Bot API expects.
+    fn form_fields(&self) -> Result<Vec<(String, String)>> {
+        let fields = match serde_json::to_value(self)? {
+            Value::Object(fields) => fields,
+            value => bail!("`{}` parameters must be an object, got: {}", Self::NAME, value),
+        };
+        let fields = fields
+            .into_iter()
+            .filter_map(|(name, value)| match value {
+                Value::Null => None,
+                Value::String(value) => Some((name, value)),
+                value => Some((name, value.to_string())),
+            })
+            .collect();
+        Ok(fields)
     }
 }
 
@@ -56,6 +132,7 @@ pub struct SetWebhook<'a> {
     pub url: String,
     pub allowed_updates: Vec<AllowedUpdate>,
     pub secret_token: Option<&'a str>,
+    pub drop_pending_updates: bool,
 }
 
 impl<'a> Method for SetWebhook<'a> {
@@ -81,12 +158,49 @@ impl<'a> SetWebhook<'a> {
         self.secret_token = Some(secret_token);
         self
     }
+
+    pub const fn drop_pending_updates(mut self, drop_pending_updates: bool) -> Self {
+        self.drop_pending_updates = drop_pending_updates;
+        self
+    }
+}
+
+/// https://core.telegram.org/bots/api#deletewebhook
+#[derive(Debug, Serialize, Default)]
+pub struct DeleteWebhook {
+    pub drop_pending_updates: bool,
+}
+
+impl Method for DeleteWebhook {
+    type Output = bool;
+
+    const NAME: &'static str = "deleteWebhook";
+}
+
+impl DeleteWebhook {
+    pub const fn drop_pending_updates(mut self) -> Self {
+        self.drop_pending_updates = true;
+        self
+    }
+}
+
+/// https://core.telegram.org/bots/api#getwebhookinfo
+#[derive(Debug, Serialize)]
+pub struct GetWebhookInfo;
+
+impl Method for GetWebhookInfo {
+    type Output = models::WebhookInfo;
+
+    const NAME: &'static str = "getWebhookInfo";
 }
 
 #[derive(Debug, Serialize)]
 pub enum AllowedUpdate {
     #[serde(rename = "message")]
     Message,
+
+    #[serde(rename = "callback_query")]
+    CallbackQuery,
 }
 
 /// https://core.telegram.org/bots/api#sendmessage
@@ -100,6 +214,12 @@ pub struct SendMessage {
 
     #[serde(skip_serializing_if = "Option::is_none")]
     pub reply_to_message_id: Option<i64>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reply_markup: Option<models::InlineKeyboardMarkup>,
+
+    /// Sends the message silently, users will receive a notification with no sound.
+    pub disable_notification: bool,
 }
 
 impl Method for SendMessage {
@@ -115,6 +235,8 @@ impl SendMessage {
             text: text.into(),
             parse_mode: None,
             reply_to_message_id: None,
+            reply_markup: None,
+            disable_notification: false,
         }
     }
 
@@ -127,6 +249,243 @@ impl SendMessage {
         self.reply_to_message_id = Some(reply_to_message_id);
         self
     }
+
+    pub fn reply_markup(mut self, reply_markup: models::InlineKeyboardMarkup) -> Self {
+        self.reply_markup = Some(reply_markup);
+        self
+    }
+
+    pub const fn disable_notification(mut self, disable_notification: bool) -> Self {
+        self.disable_notification = disable_notification;
+        self
+    }
+}
+
+/// https://core.telegram.org/bots/api#editmessagetext
+#[derive(Debug, Serialize)]
+pub struct EditMessageText {
+    pub chat_id: models::ChatId,
+    pub message_id: i64,
+    pub text: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parse_mode: Option<models::ParseMode>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reply_markup: Option<models::InlineKeyboardMarkup>,
+}
+
+impl Method for EditMessageText {
+    type Output = models::Message;
+
+    const NAME: &'static str = "editMessageText";
+}
+
+impl EditMessageText {
+    pub fn new(
+        chat_id: impl Into<models::ChatId>,
+        message_id: i64,
+        text: impl Into<String>,
+    ) -> Self {
+        Self {
+            chat_id: chat_id.into(),
+            message_id,
+            text: text.into(),
+            parse_mode: None,
+            reply_markup: None,
+        }
+    }
+
+    pub const fn parse_mode(mut self, parse_mode: models::ParseMode) -> Self {
+        self.parse_mode = Some(parse_mode);
+        self
+    }
+
+    pub fn reply_markup(mut self, reply_markup: models::InlineKeyboardMarkup) -> Self {
+        self.reply_markup = Some(reply_markup);
+        self
+    }
+}
+
+/// https://core.telegram.org/bots/api#editmessagereplymarkup
+#[derive(Debug, Serialize)]
+pub struct EditMessageReplyMarkup {
+    pub chat_id: models::ChatId,
+    pub message_id: i64,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reply_markup: Option<models::InlineKeyboardMarkup>,
+}
+
+impl Method for EditMessageReplyMarkup {
+    type Output = models::Message;
+
+    const NAME: &'static str = "editMessageReplyMarkup";
+}
+
+impl EditMessageReplyMarkup {
+    /// Remove the inline keyboard unless `reply_markup` is set.
+    pub fn new(chat_id: impl Into<models::ChatId>, message_id: i64) -> Self {
+        Self {
+            chat_id: chat_id.into(),
+            message_id,
+            reply_markup: None,
+        }
+    }
+
+    pub fn reply_markup(mut self, reply_markup: models::InlineKeyboardMarkup) -> Self {
+        self.reply_markup = Some(reply_markup);
+        self
+    }
+}
+
+/// https://core.telegram.org/bots/api#answercallbackquery
+#[derive(Debug, Serialize)]
+pub struct AnswerCallbackQuery {
+    pub callback_query_id: String,
+
+    /// Text of the notification. If not specified, nothing will be shown to the user.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+
+    /// Show an alert instead of a notification at the top of the chat screen.
+    pub show_alert: bool,
+}
+
+impl Method for AnswerCallbackQuery {
+    type Output = bool;
+
+    const NAME: &'static str = "answerCallbackQuery";
+}
+
+impl AnswerCallbackQuery {
+    pub fn new(callback_query_id: impl Into<String>) -> Self {
+        Self {
+            callback_query_id: callback_query_id.into(),
+            text: None,
+            show_alert: false,
+        }
+    }
+
+    pub fn text(mut self, text: impl Into<String>) -> Self {
+        self.text = Some(text.into());
+        self
+    }
+
+    pub const fn show_alert(mut self) -> Self {
+        self.show_alert = true;
+        self
+    }
+}
+
+/// https://core.telegram.org/bots/api#senddocument
+#[derive(Debug, Serialize)]
+pub struct SendDocument {
+    pub chat_id: models::ChatId,
+
+    #[serde(skip)]
+    pub document: models::InputFile,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub caption: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parse_mode: Option<models::ParseMode>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reply_markup: Option<models::InlineKeyboardMarkup>,
+}
+
+impl Method for SendDocument {
+    type Output = models::Message;
+
+    const NAME: &'static str = "sendDocument";
+
+    fn files(&self) -> Vec<(&'static str, &models::InputFile)> {
+        vec![("document", &self.document)]
+    }
+}
+
+impl SendDocument {
+    pub fn new(chat_id: impl Into<models::ChatId>, document: models::InputFile) -> Self {
+        Self {
+            chat_id: chat_id.into(),
+            document,
+            caption: None,
+            parse_mode: None,
+            reply_markup: None,
+        }
+    }
+
+    pub fn caption(mut self, caption: impl Into<String>) -> Self {
+        self.caption = Some(caption.into());
+        self
+    }
+
+    pub const fn parse_mode(mut self, parse_mode: models::ParseMode) -> Self {
+        self.parse_mode = Some(parse_mode);
+        self
+    }
+
+    pub fn reply_markup(mut self, reply_markup: models::InlineKeyboardMarkup) -> Self {
+        self.reply_markup = Some(reply_markup);
+        self
+    }
+}
+
+/// https://core.telegram.org/bots/api#sendphoto
+#[derive(Debug, Serialize)]
+pub struct SendPhoto {
+    pub chat_id: models::ChatId,
+
+    #[serde(skip)]
+    pub photo: models::InputFile,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub caption: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parse_mode: Option<models::ParseMode>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reply_markup: Option<models::InlineKeyboardMarkup>,
+}
+
+impl Method for SendPhoto {
+    type Output = models::Message;
+
+    const NAME: &'static str = "sendPhoto";
+
+    fn files(&self) -> Vec<(&'static str, &models::InputFile)> {
+        vec![("photo", &self.photo)]
+    }
+}
+
+impl SendPhoto {
+    pub fn new(chat_id: impl Into<models::ChatId>, photo: models::InputFile) -> Self {
+        Self {
+            chat_id: chat_id.into(),
+            photo,
+            caption: None,
+            parse_mode: None,
+            reply_markup: None,
+        }
+    }
+
+    pub fn caption(mut self, caption: impl Into<String>) -> Self {
+        self.caption = Some(caption.into());
+        self
+    }
+
+    pub const fn parse_mode(mut self, parse_mode: models::ParseMode) -> Self {
+        self.parse_mode = Some(parse_mode);
+        self
+    }
+
+    pub fn reply_markup(mut self, reply_markup: models::InlineKeyboardMarkup) -> Self {
+        self.reply_markup = Some(reply_markup);
+        self
+    }
 }
 
 /// https://core.telegram.org/bots/api#setmycommands
@@ -149,7 +508,7 @@ impl SetMyCommands {
 }
 
 /// Shared location parameters.
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Clone)]
 pub struct Location {
     pub chat_id: models::ChatId,
     pub latitude: f64,
@@ -193,6 +552,9 @@ pub struct SendLocation {
 
     #[serde_as(as = "Option<DurationSeconds<u64>>")]
     pub live_period: Option<time::Duration>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reply_markup: Option<models::InlineKeyboardMarkup>,
 }
 
 impl Method for SendLocation {
@@ -206,6 +568,7 @@ impl SendLocation {
         Self {
             location,
             live_period: None,
+            reply_markup: None,
         }
     }
 
@@ -213,6 +576,11 @@ impl SendLocation {
         self.live_period = Some(live_period);
         self
     }
+
+    pub fn reply_markup(mut self, reply_markup: models::InlineKeyboardMarkup) -> Self {
+        self.reply_markup = Some(reply_markup);
+        self
+    }
 }
 
 /// https://core.telegram.org/bots/api#editmessagelivelocation
@@ -224,6 +592,9 @@ pub struct EditMessageLiveLocation {
 
     #[serde(flatten)]
     pub location: Location,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reply_markup: Option<models::InlineKeyboardMarkup>,
 }
 
 impl Method for EditMessageLiveLocation {
@@ -238,8 +609,14 @@ impl EditMessageLiveLocation {
             chat_id,
             message_id,
             location,
+            reply_markup: None,
         }
     }
+
+    pub fn reply_markup(mut self, reply_markup: models::InlineKeyboardMarkup) -> Self {
+        self.reply_markup = Some(reply_markup);
+        self
+    }
 }
 
 #[derive(Debug, Serialize)]
@@ -267,6 +644,9 @@ impl DeleteMessage {
 pub struct StopMessageLiveLocation {
     pub chat_id: models::ChatId,
     pub message_id: i64,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reply_markup: Option<models::InlineKeyboardMarkup>,
 }
 
 impl Method for StopMessageLiveLocation {
@@ -275,6 +655,21 @@ impl Method for StopMessageLiveLocation {
     const NAME: &'static str = "stopMessageLiveLocation";
 }
 
+impl StopMessageLiveLocation {
+    pub fn new(chat_id: impl Into<models::ChatId>, message_id: i64) -> Self {
+        Self {
+            chat_id: chat_id.into(),
+            message_id,
+            reply_markup: None,
+        }
+    }
+
+    pub fn reply_markup(mut self, reply_markup: models::InlineKeyboardMarkup) -> Self {
+        self.reply_markup = Some(reply_markup);
+        self
+    }
+}
+
 #[derive(Debug, Serialize)]
 pub struct PinChatMessage {
     pub chat_id: models::ChatId,
@@ -324,3 +719,35 @@ impl UnpinChatMessage {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn form_fields_ok() -> Result<()> {
+        let method = SendDocument::new(42, models::InputFile::new("export.csv", b"a,b".to_vec()))
+            .caption("Export")
+            .parse_mode(models::ParseMode::Html)
+            .reply_markup(models::InlineKeyboardMarkup::default().row([
+                models::InlineKeyboardButton::callback_data("Refresh", "refresh"),
+            ]));
+        let mut fields = method.form_fields()?;
+        fields.sort();
+        assert_eq!(
+            fields,
+            [
+                ("caption".to_string(), "Export".to_string()),
+                ("chat_id".to_string(), "42".to_string()),
+                ("parse_mode".to_string(), "HTML".to_string()),
+                (
+                    "reply_markup".to_string(),
+                    r#"{"inline_keyboard":[[{"callback_data":"refresh","text":"Refresh"}]]}"#
+                        .to_string()
+                ),
+            ]
+        );
+        assert_eq!(method.files().len(), 1);
+        Ok(())
+    }
+}
@@ -0,0 +1,238 @@
+//! Local mock Bot API server for tests.
+//!
+//! The server records every method call and replies with the scripted responses.
+//! Both JSON and `multipart/form-data` calls are supported.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time;
+
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use poem::listener::{Acceptor, Listener, TcpListener};
+use poem::web::{Data, Json, Multipart, Path};
+use poem::{handler, post, EndpointExt, FromRequest, Request, RequestBody, Route, Server};
+use serde_json::{json, Value};
+use tracing::{debug, info};
+
+use crate::api::BotApi;
+use crate::models;
+
+/// Recorded method call.
+#[derive(Debug, Clone)]
+pub struct Call {
+    /// Method name, for example: `sendMessage`.
+    pub method: String,
+
+    /// JSON parameters.
+    ///
+    /// For multipart calls, these are the text fields. The ones which are valid JSON
+    /// get parsed, and the rest are kept as strings.
+    pub params: Value,
+
+    /// Uploaded files by the field name, multipart calls only.
+    pub files: HashMap<String, models::InputFile>,
+}
+
+#[derive(Default)]
+struct State {
+    calls: Vec<Call>,
+
+    /// Scripted responses per method name, served in order.
+    responses: HashMap<String, VecDeque<Value>>,
+}
+
+/// Mock Bot API server which is running in background.
+pub struct MockServer {
+    base_url: String,
+    state: Arc<Mutex<State>>,
+}
+
+impl MockServer {
+    /// Token, which the mock server expects.
+    pub const TOKEN: &'static str = "42:mock";
+
+    /// Start the server on a random local port.
+    pub async fn start() -> Result<Self> {
+        let acceptor = TcpListener::bind("127.0.0.1:0").into_acceptor().await?;
+        let address = acceptor
+            .local_addr()
+            .first()
+            .and_then(|address| address.as_socket_addr().copied())
+            .ok_or_else(|| anyhow!("failed to get the mock server address"))?;
+        let base_url = format!("http://{}", address);
+        info!(base_url, "starting the mock Bot API server…");
+
+        let state = Arc::new(Mutex::new(State::default()));
+        let app = Route::new()
+            .at("/:token/:method", post(post_method))
+            .data(state.clone());
+        async_std::task::spawn(Server::new_with_acceptor(acceptor).run(app));
+
+        Ok(Self { base_url, state })
+    }
+
+    /// Create the client which is connected to the mock server.
+    pub fn bot_api(&self) -> Result<BotApi> {
+        BotApi::new(&self.base_url, Self::TOKEN, time::Duration::from_secs(5))
+    }
+
+    /// Script a successful response for the next call of the method.
+    pub fn respond(&self, method: &str, result: Value) -> &Self {
+        let mut response = json!({"ok": true});
+        response["result"] = result;
+        self.push_response(method, response)
+    }
+
+    /// Script an error response for the next call of the method.
+    pub fn respond_error(&self, method: &str, error_code: i32, description: &str) -> &Self {
+        self.push_response(
+            method,
+            json!({"ok": false, "error_code": error_code, "description": description}),
+        )
+    }
+
+    /// All the calls recorded so far.
+    pub fn calls(&self) -> Vec<Call> {
+        self.state.lock().unwrap().calls.clone()
+    }
+
+    /// Calls of the specified method recorded so far.
+    pub fn calls_of(&self, method: &str) -> Vec<Call> {
+        self.calls()
+            .into_iter()
+            .filter(|call| call.method == method)
+            .collect()
+    }
+
+    fn push_response(&self, method: &str, response: Value) -> &Self {
+        self.state
+            .lock()
+            .unwrap()
+            .responses
+            .entry(method.to_string())
+            .or_default()
+            .push_back(response);
+        self
+    }
+}
+
+/// Method call parameters, either from the JSON or the `multipart/form-data` body.
+struct Params {
+    params: Value,
+    files: HashMap<String, models::InputFile>,
+}
+
+#[async_trait]
+impl<'a> FromRequest<'a> for Params {
+    async fn from_request(request: &'a Request, body: &mut RequestBody) -> poem::Result<Self> {
+        let is_multipart = request
+            .content_type()
+            .is_some_and(|content_type| content_type.starts_with("multipart/form-data"));
+        if !is_multipart {
+            let Json(params) = Json::from_request(request, body).await?;
+            return Ok(Self {
+                params,
+                files: HashMap::new(),
+            });
+        }
+
+        let mut multipart = Multipart::from_request(request, body).await?;
+        let mut params = json!({});
+        let mut files = HashMap::new();
+        while let Some(field) = multipart.next_field().await? {
+            let name = field.name().unwrap_or_default().to_string();
+            if let Some(file_name) = field.file_name().map(ToString::to_string) {
+                let mime_type = field.content_type().map(ToString::to_string);
+                let file = models::InputFile {
+                    file_name,
+                    content: field.bytes().await?,
+                    mime_type,
+                };
+                files.insert(name, file);
+            } else {
+                let text = field.text().await?;
+                params[name] = serde_json::from_str(&text).unwrap_or(Value::String(text));
+            }
+        }
+        Ok(Self { params, files })
+    }
+}
+
+#[handler]
+async fn post_method(
+    Path((token, method)): Path<(String, String)>,
+    Params { params, files }: Params,
+    state: Data<&Arc<Mutex<State>>>,
+) -> Json<Value> {
+    debug!(method, ?params, "mock call");
+    if token != format!("bot{}", MockServer::TOKEN) {
+        return Json(json!({"ok": false, "error_code": 401, "description": "Unauthorized"}));
+    }
+    let mut state = state.lock().unwrap();
+    let response = state
+        .responses
+        .get_mut(&method)
+        .and_then(VecDeque::pop_front)
+        .unwrap_or_else(|| {
+            json!({
+                "ok": false,
+                "error_code": 501,
+                "description": format!("no scripted response for `{}`", method),
+            })
+        });
+    state.calls.push(Call {
+        method,
+        params,
+        files,
+    });
+    Json(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::methods::{GetMe, Method, SendDocument, SendMessage};
+
+    #[async_std::test]
+    async fn mock_server_ok() -> Result<()> {
+        let server = MockServer::start().await?;
+        let bot_api = server.bot_api()?;
+        server.respond("getMe", json!({"id": 42, "first_name": "Mock", "username": "MockBot"}));
+        assert_eq!(bot_api.last_success_at(), None);
+
+        let me = GetMe.call(&bot_api).await?;
+        assert!(bot_api.last_success_at().is_some());
+        assert_eq!(me.id, 42);
+        assert_eq!(me.username.as_deref(), Some("MockBot"));
+
+        assert!(SendMessage::new(1, "hello").call(&bot_api).await.is_err());
+        let calls = server.calls_of("sendMessage");
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].params["text"], "hello");
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn multipart_ok() -> Result<()> {
+        let server = MockServer::start().await?;
+        let bot_api = server.bot_api()?;
+        server.respond("sendDocument", json!({"message_id": 1, "chat": {"id": 42}, "date": 0}));
+
+        let document = models::InputFile::new("export.csv", b"a,b".to_vec()).mime_type("text/csv");
+        SendDocument::new(42, document)
+            .caption("Export")
+            .call(&bot_api)
+            .await?;
+
+        let calls = server.calls_of("sendDocument");
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].params["chat_id"], 42);
+        assert_eq!(calls[0].params["caption"], "Export");
+        let document = &calls[0].files["document"];
+        assert_eq!(document.file_name, "export.csv");
+        assert_eq!(document.content, b"a,b");
+        assert_eq!(document.mime_type.as_deref(), Some("text/csv"));
+        Ok(())
+    }
+}
@@ -1,6 +1,8 @@
 use std::borrow::Cow;
+use std::fmt;
+use std::fmt::{Debug, Formatter};
 
-use anyhow::{anyhow, Result};
+use anyhow::Result;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
@@ -23,11 +25,40 @@ impl<T> From<Response<T>> for Result<T> {
             Response::Err {
                 error_code,
                 description,
-            } => Err(anyhow!("error {}: {}", error_code, description)),
+            } => Err(ApiError {
+                error_code,
+                description,
+            }
+            .into()),
         }
     }
 }
 
+/// Error returned by Bot API.
+///
+/// Callers may `downcast_ref` the method call error to inspect it.
+#[derive(Debug)]
+pub struct ApiError {
+    pub error_code: i32,
+    pub description: String,
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "error {}: {}", self.error_code, self.description)
+    }
+}
+
+impl std::error::Error for ApiError {}
+
+impl ApiError {
+    /// Check whether the message can no longer be edited, for example, because its live period is over.
+    pub fn is_message_not_editable(&self) -> bool {
+        self.description.contains("message can't be edited")
+            || self.description.contains("message to edit not found")
+    }
+}
+
 #[derive(Debug, Deserialize)]
 pub struct User {
     /// Unique identifier for this user or bot.
@@ -41,6 +72,24 @@ pub struct User {
     pub username: Option<String>,
 }
 
+/// https://core.telegram.org/bots/api#webhookinfo
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct WebhookInfo {
+    /// Webhook URL, may be empty if webhook is not set up.
+    pub url: String,
+
+    /// Number of updates awaiting delivery.
+    pub pending_update_count: u32,
+
+    /// Unix time for the most recent error that happened when trying to deliver an update via webhook.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_error_date: Option<i64>,
+
+    /// Error message in human-readable format for the most recent error.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_error_message: Option<String>,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct Chat {
     /// Unique identifier for this chat.
@@ -83,6 +132,9 @@ pub enum UpdatePayload {
     #[serde(rename = "message")]
     Message(Message),
 
+    #[serde(rename = "callback_query")]
+    CallbackQuery(CallbackQuery),
+
     #[serde(rename = "my_chat_member")]
     MyChatMember(Value), // FIXME
 }
@@ -100,6 +152,105 @@ pub struct Message {
 
     #[serde(default)]
     pub text: Option<String>,
+
+    /// Special entities like usernames, URLs, bot commands, etc. that appear in the text.
+    #[serde(default)]
+    pub entities: Vec<MessageEntity>,
+}
+
+/// https://core.telegram.org/bots/api#messageentity
+#[derive(Debug, Deserialize)]
+pub struct MessageEntity {
+    #[serde(rename = "type")]
+    pub kind: MessageEntityKind,
+
+    /// Offset in UTF-16 code units to the start of the entity.
+    pub offset: usize,
+
+    /// Length of the entity in UTF-16 code units.
+    pub length: usize,
+}
+
+#[derive(Debug, Deserialize, PartialEq, Eq)]
+pub enum MessageEntityKind {
+    #[serde(rename = "bot_command")]
+    BotCommand,
+
+    #[serde(other)]
+    Other,
+}
+
+/// https://core.telegram.org/bots/api#callbackquery
+#[derive(Debug, Deserialize)]
+pub struct CallbackQuery {
+    /// Unique identifier for this query.
+    pub id: String,
+
+    /// Sender.
+    pub from: User,
+
+    /// Message with the callback button that originated the query.
+    #[serde(default)]
+    pub message: Option<Message>,
+
+    /// Global identifier, uniquely corresponding to the chat to which the message was sent.
+    pub chat_instance: String,
+
+    /// Data associated with the callback button.
+    #[serde(default)]
+    pub data: Option<String>,
+}
+
+/// https://core.telegram.org/bots/api#inlinekeyboardmarkup
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct InlineKeyboardMarkup {
+    /// Array of button rows.
+    pub inline_keyboard: Vec<Vec<InlineKeyboardButton>>,
+}
+
+impl InlineKeyboardMarkup {
+    /// Append a new row of buttons.
+    pub fn row(mut self, buttons: impl IntoIterator<Item = InlineKeyboardButton>) -> Self {
+        self.inline_keyboard.push(buttons.into_iter().collect());
+        self
+    }
+}
+
+/// https://core.telegram.org/bots/api#inlinekeyboardbutton
+#[derive(Debug, Serialize, Clone)]
+pub struct InlineKeyboardButton {
+    /// Label text on the button.
+    pub text: String,
+
+    #[serde(flatten)]
+    pub action: InlineKeyboardButtonAction,
+}
+
+impl InlineKeyboardButton {
+    pub fn callback_data(text: impl Into<String>, data: impl Into<String>) -> Self {
+        Self {
+            text: text.into(),
+            action: InlineKeyboardButtonAction::CallbackData(data.into()),
+        }
+    }
+
+    pub fn url(text: impl Into<String>, url: impl Into<String>) -> Self {
+        Self {
+            text: text.into(),
+            action: InlineKeyboardButtonAction::Url(url.into()),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub enum InlineKeyboardButtonAction {
+    /// Data to be sent in a callback query to the bot when button is pressed, 1-64 bytes.
+    #[serde(rename = "callback_data")]
+    CallbackData(String),
+
+    /// HTTP or `tg://` URL to be opened when the button is pressed.
+    #[serde(rename = "url")]
+    Url(String),
 }
 
 #[derive(Debug, Serialize)]
@@ -111,6 +262,41 @@ pub struct BotCommand {
     pub description: Cow<'static, str>,
 }
 
+/// File to be uploaded using `multipart/form-data`.
+///
+/// https://core.telegram.org/bots/api#inputfile
+#[derive(Clone)]
+pub struct InputFile {
+    pub file_name: String,
+    pub content: Vec<u8>,
+    pub mime_type: Option<String>,
+}
+
+impl Debug for InputFile {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("InputFile")
+            .field("file_name", &self.file_name)
+            .field("content.len", &self.content.len())
+            .field("mime_type", &self.mime_type)
+            .finish()
+    }
+}
+
+impl InputFile {
+    pub fn new(file_name: impl Into<String>, content: Vec<u8>) -> Self {
+        Self {
+            file_name: file_name.into(),
+            content,
+            mime_type: None,
+        }
+    }
+
+    pub fn mime_type(mut self, mime_type: impl Into<String>) -> Self {
+        self.mime_type = Some(mime_type.into());
+        self
+    }
+}
+
 /// https://core.telegram.org/bots/api#formatting-options
 #[derive(Debug, Serialize, Clone, Copy)]
 pub enum ParseMode {
@@ -124,7 +310,7 @@ pub enum ParseMode {
 
 #[cfg(test)]
 mod tests {
-    use anyhow::Result;
+    use anyhow::{anyhow, Result};
     use serde_json::from_str;
 
     use super::*;
@@ -135,4 +321,47 @@ mod tests {
             from_str::<Response<Vec<Update>>>(r#"{"ok": true, "result": []}"#)?.into();
         Ok(())
     }
+
+    #[test]
+    fn api_error_ok() {
+        let result: Result<Message> = from_str::<Response<Message>>(
+            r#"{"ok": false, "error_code": 400, "description": "Bad Request: message can't be edited"}"#,
+        )
+        .unwrap()
+        .into();
+        let error = result.unwrap_err();
+        let error = error.downcast_ref::<ApiError>().unwrap();
+        assert_eq!(error.error_code, 400);
+        assert!(error.is_message_not_editable());
+    }
+
+    #[test]
+    fn callback_query_ok() -> Result<()> {
+        let update: Update = from_str(
+            // language=json
+            r#"{"update_id":1,"callback_query":{"id":"42","from":{"id":1,"is_bot":false,"first_name":"Tester"},"message":{"message_id":2,"chat":{"id":3,"type":"private"}},"chat_instance":"-1","data":"refresh"}}"#,
+        )?;
+        match update.payload {
+            UpdatePayload::CallbackQuery(query) => {
+                assert_eq!(query.id, "42");
+                assert_eq!(query.data.as_deref(), Some("refresh"));
+                assert_eq!(query.message.map(|message| message.id), Some(2));
+                Ok(())
+            }
+            payload => Err(anyhow!("incorrect payload: {:?}", payload)),
+        }
+    }
+
+    #[test]
+    fn inline_keyboard_markup_ok() -> Result<()> {
+        let markup = InlineKeyboardMarkup::default().row([
+            InlineKeyboardButton::callback_data("Refresh", "refresh"),
+            InlineKeyboardButton::url("Tractive", "https://my.tractive.com"),
+        ]);
+        assert_eq!(
+            serde_json::to_string(&markup)?,
+            r#"{"inline_keyboard":[[{"text":"Refresh","callback_data":"refresh"},{"text":"Tractive","url":"https://my.tractive.com"}]]}"#,
+        );
+        Ok(())
+    }
 }
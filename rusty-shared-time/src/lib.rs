@@ -0,0 +1,23 @@
+#![warn(
+    clippy::all,
+    clippy::missing_const_for_fn,
+    clippy::trivially_copy_pass_by_ref,
+    clippy::map_unwrap_or,
+    clippy::explicit_into_iter_loop,
+    clippy::unused_self,
+    clippy::needless_pass_by_value
+)]
+
+//! Wall clock helpers, shared by the microservices and the libraries.
+//!
+//! The crate has no dependencies, so that the libraries can use it freely.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Current Unix time in seconds.
+pub fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs() as i64
+}
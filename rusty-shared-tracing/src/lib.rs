@@ -8,9 +8,18 @@
     clippy::needless_pass_by_value
 )]
 
+pub mod propagation;
+
 use std::borrow::Cow;
+use std::collections::HashMap;
 
-use anyhow::Result;
+use anyhow::{Context, Result};
+use opentelemetry::sdk::propagation::TraceContextPropagator;
+use opentelemetry::sdk::{trace, Resource};
+use opentelemetry::{global, KeyValue};
+use opentelemetry_otlp::WithExportConfig;
+use rand::random;
+use rusty_shared_opts::log::LogFormat;
 use sentry::integrations::tracing::EventFilter;
 use sentry::{ClientInitGuard, ClientOptions};
 use tracing::Level;
@@ -18,14 +27,34 @@ use tracing_subscriber::layer::SubscriberExt;
 use tracing_subscriber::util::SubscriberInitExt;
 use tracing_subscriber::{EnvFilter, Layer};
 
+/// Flushes the pending Sentry events and spans on drop.
+pub struct Guard {
+    _sentry_guard: ClientInitGuard,
+    is_otlp_enabled: bool,
+}
+
+impl Drop for Guard {
+    fn drop(&mut self) {
+        if self.is_otlp_enabled {
+            global::shutdown_tracer_provider();
+        }
+    }
+}
+
 pub fn init(
     sentry_opts: rusty_shared_opts::sentry::Opts,
+    log_opts: rusty_shared_opts::log::Opts,
+    otlp_opts: rusty_shared_opts::otlp::Opts,
     app_name: &str,
-) -> Result<ClientInitGuard> {
+) -> Result<Guard> {
     let guard = sentry::init((
         sentry_opts.dsn,
         ClientOptions {
             release: Some(Cow::Borrowed(env!("CARGO_PKG_VERSION"))),
+            environment: sentry_opts.environment.map(Cow::Owned),
+            // The contexts integration falls back to the hostname.
+            server_name: sentry_opts.server_name.map(Cow::Owned),
+            sample_rate: sentry_opts.sample_rate,
             traces_sample_rate: sentry_opts.traces_sample_rate,
             ..Default::default()
         },
@@ -35,25 +64,71 @@ pub fn init(
         scope.set_tag("app.name", app_name);
     });
 
+    // A dropped root span doesn't start a transaction, which saves the quota in the hot loops.
+    let span_sample_rates: HashMap<String, f32> = sentry_opts
+        .span_sample_rates
+        .into_iter()
+        .map(|span_sample_rate| (span_sample_rate.name, span_sample_rate.rate))
+        .collect();
     let sentry_layer = sentry::integrations::tracing::layer()
         .event_filter(|metadata| match metadata.level() {
             &Level::ERROR | &Level::WARN => EventFilter::Event,
             &Level::INFO | &Level::DEBUG | &Level::TRACE => EventFilter::Breadcrumb,
         })
-        .span_filter(|metadata| {
+        .span_filter(move |metadata| {
             matches!(metadata.level(), &Level::ERROR | &Level::WARN | &Level::INFO | &Level::DEBUG)
+                && span_sample_rates
+                    .get(metadata.name())
+                    .is_none_or(|rate| random::<f32>() < *rate)
         });
 
     let format_filter =
         EnvFilter::try_from_env("RUSTY_HOME_LOG").or_else(|_| EnvFilter::try_new("info"))?;
-    let format_layer = tracing_subscriber::fmt::layer()
-        .without_time()
-        .with_filter(format_filter);
+    let format_layer = match log_opts.log_format {
+        LogFormat::Pretty => tracing_subscriber::fmt::layer().without_time().boxed(),
+        LogFormat::Json => tracing_subscriber::fmt::layer()
+            .json()
+            .with_current_span(true)
+            .with_span_list(true)
+            .boxed(),
+        LogFormat::Journald => tracing_journald::layer()
+            .context("failed to connect to journald")?
+            .with_syslog_identifier(app_name.to_string())
+            .boxed(),
+    }
+    .with_filter(format_filter);
+
+    let otlp_layer = match otlp_opts.otlp_endpoint {
+        Some(endpoint) => {
+            global::set_text_map_propagator(TraceContextPropagator::new());
+            let tracer = opentelemetry_otlp::new_pipeline()
+                .tracing()
+                .with_exporter(
+                    opentelemetry_otlp::new_exporter()
+                        .http()
+                        .with_endpoint(endpoint),
+                )
+                .with_trace_config(trace::config().with_resource(Resource::new([KeyValue::new(
+                    "service.name",
+                    app_name.to_string(),
+                )])))
+                .install_batch(opentelemetry::runtime::AsyncStd)
+                .context("failed to install the OTLP pipeline")?;
+            Some(tracing_opentelemetry::layer().with_tracer(tracer))
+        }
+        None => None,
+    };
+
+    let is_otlp_enabled = otlp_layer.is_some();
 
     tracing_subscriber::Registry::default()
         .with(sentry_layer)
         .with(format_layer)
+        .with(otlp_layer)
         .init();
 
-    Ok(guard)
+    Ok(Guard {
+        _sentry_guard: guard,
+        is_otlp_enabled,
+    })
 }
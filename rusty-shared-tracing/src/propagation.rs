@@ -0,0 +1,57 @@
+//! Trace context propagation through the Redis stream entries.
+
+use std::collections::HashMap;
+
+use opentelemetry::global;
+use tracing::Span;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+/// Trace context fields of the current span, which the producer adds to the entry.
+///
+/// Empty unless the OTLP export is enabled.
+pub fn inject_context() -> Vec<(String, String)> {
+    let mut fields = HashMap::new();
+    global::get_text_map_propagator(|propagator| {
+        propagator.inject_context(&Span::current().context(), &mut fields);
+    });
+    fields.into_iter().collect()
+}
+
+/// Make the span a child of the trace context, which the consumer has read from the entry.
+pub fn set_parent(span: &Span, fields: &HashMap<String, String>) {
+    let context = global::get_text_map_propagator(|propagator| propagator.extract(fields));
+    span.set_parent(context);
+}
+
+#[cfg(test)]
+mod tests {
+    use opentelemetry::sdk::propagation::TraceContextPropagator;
+    use opentelemetry::sdk::trace::TracerProvider;
+    use opentelemetry::trace::{TraceContextExt, TracerProvider as _};
+    use tracing::info_span;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    use super::*;
+
+    #[test]
+    fn round_trip_ok() {
+        global::set_text_map_propagator(TraceContextPropagator::new());
+        let provider = TracerProvider::builder().build();
+        let tracer = provider.tracer("test");
+        let subscriber = tracing_subscriber::Registry::default()
+            .with(tracing_opentelemetry::layer().with_tracer(tracer));
+
+        tracing::subscriber::with_default(subscriber, || {
+            let producer_span = info_span!("producer");
+            let fields = producer_span.in_scope(inject_context);
+            assert!(fields.iter().any(|(key, _)| key == "traceparent"));
+
+            let consumer_span = info_span!("consumer");
+            set_parent(&consumer_span, &fields.into_iter().collect());
+            assert_eq!(
+                consumer_span.context().span().span_context().trace_id(),
+                producer_span.context().span().span_context().trace_id(),
+            );
+        });
+    }
+}
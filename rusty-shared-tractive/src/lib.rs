@@ -31,6 +31,12 @@ pub struct HardwareEntry {
 
     #[kv(rename = "battery")]
     pub battery_level: u8,
+
+    /// Tractive sends the charging state alongside the hardware status,
+    /// so it gets filled in separately.
+    #[kv(optional, default(), rename = "charging")]
+    #[serde(skip)]
+    pub is_charging: Option<bool>,
 }
 
 #[derive(IntoVec, FromMapping, Debug)]
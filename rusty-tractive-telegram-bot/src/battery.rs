@@ -0,0 +1,121 @@
+//! Battery drain estimation.
+
+use rusty_shared_tractive::HardwareEntry;
+
+/// Discharge rate, estimated from the recent hardware history.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct DrainEstimate {
+    /// Percentage points per hour.
+    pub drain_per_hour: f64,
+
+    /// Estimated time until the battery is empty, `None` when it's not discharging.
+    pub hours_left: Option<f64>,
+}
+
+impl DrainEstimate {
+    /// Minimal total discharging time, in seconds, which gives a meaningful estimate.
+    const MIN_DISCHARGING_TIME: i64 = 3600;
+
+    /// Estimate the drain from the history, which must be sorted newest first.
+    ///
+    /// Only the entries within `window_secs` from the newest one are taken into account.
+    /// Charging periods are skipped.
+    pub fn from_history(history: &[HardwareEntry], window_secs: i64) -> Option<Self> {
+        let last_entry = history.first()?;
+        let since = last_entry.timestamp.timestamp() - window_secs;
+
+        let (mut total_drop, mut total_secs) = (0, 0);
+        for pair in history.windows(2) {
+            let (newer, older) = (&pair[0], &pair[1]);
+            if older.timestamp.timestamp() < since {
+                break;
+            }
+            if newer.is_charging == Some(true)
+                || older.is_charging == Some(true)
+                || newer.battery_level > older.battery_level
+            {
+                continue;
+            }
+            total_drop += i64::from(older.battery_level - newer.battery_level);
+            total_secs += newer.timestamp.timestamp() - older.timestamp.timestamp();
+        }
+        if total_secs < Self::MIN_DISCHARGING_TIME {
+            return None;
+        }
+
+        let drain_per_hour = total_drop as f64 * 3600.0 / total_secs as f64;
+        Some(Self {
+            drain_per_hour,
+            hours_left: (drain_per_hour > 0.0)
+                .then(|| f64::from(last_entry.battery_level) / drain_per_hour),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+
+    use super::*;
+
+    fn entry(timestamp: i64, battery_level: u8, is_charging: Option<bool>) -> HardwareEntry {
+        HardwareEntry {
+            timestamp: Utc.timestamp(timestamp, 0),
+            battery_level,
+            is_charging,
+        }
+    }
+
+    #[test]
+    fn discharging_ok() {
+        let history = [
+            entry(7200, 48, Some(false)),
+            entry(3600, 49, Some(false)),
+            entry(0, 50, Some(false)),
+        ];
+        assert_eq!(
+            DrainEstimate::from_history(&history, 86400),
+            Some(DrainEstimate {
+                drain_per_hour: 1.0,
+                hours_left: Some(48.0),
+            }),
+        );
+    }
+
+    #[test]
+    fn charging_skipped_ok() {
+        let history = [
+            entry(4 * 3600, 88, None),
+            entry(3 * 3600, 90, Some(false)),
+            entry(2 * 3600, 80, Some(true)),
+            entry(3600, 50, Some(false)),
+            entry(0, 52, Some(false)),
+        ];
+        let estimate = DrainEstimate::from_history(&history, 86400).unwrap();
+        assert_eq!(estimate.drain_per_hour, 2.0);
+        assert_eq!(estimate.hours_left, Some(44.0));
+    }
+
+    #[test]
+    fn window_ok() {
+        let history = [
+            entry(7200, 50, None),
+            entry(3600, 50, None),
+            entry(0, 90, None),
+        ];
+        assert_eq!(
+            DrainEstimate::from_history(&history, 3600),
+            Some(DrainEstimate {
+                drain_per_hour: 0.0,
+                hours_left: None,
+            }),
+        );
+    }
+
+    #[test]
+    fn not_enough_data_ok() {
+        assert_eq!(DrainEstimate::from_history(&[], 86400), None);
+        let history = [entry(600, 49, None), entry(0, 50, None)];
+        assert_eq!(DrainEstimate::from_history(&history, 86400), None);
+    }
+}
@@ -1,66 +1,202 @@
 //! Implements the Telegram bot logic.
 
-use std::borrow::Cow;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Arc;
 
+use anyhow::Error;
+use futures::future::{try_join, BoxFuture};
+use futures::FutureExt;
 use poem::http::StatusCode;
-use poem::listener::TcpListener;
+use poem::listener::{Listener, TcpListener};
 use poem::middleware::AddData;
 use poem::web::{Data, Json, TypedHeader};
-use poem::{get, handler, post, EndpointExt, Route, Server};
+use poem::{get, handler, post, EndpointExt, IntoResponse, Route, Server};
+use rusty_shared_heartbeat::status::ServiceStatus;
+use rusty_shared_opts::shutdown::Shutdown;
+use rusty_shared_opts::systemd;
+use rusty_shared_redis::Redis;
 use rusty_shared_telegram::api::BotApi;
+use rusty_shared_telegram::commands::{CommandRouter, ReplyInvalidArgs};
 use rusty_shared_telegram::headers::SecretToken;
 use rusty_shared_telegram::methods::Method;
 use rusty_shared_telegram::{methods, models};
+use rusty_shared_time::now;
 use secstr::SecUtf8;
+use serde::Serialize;
 
+use crate::battery::DrainEstimate;
+use crate::live_location::LiveLocations;
+use crate::locale::Catalog;
 use crate::middleware::TracingMiddleware;
+use crate::opts::{NotificationOpts, TemplateArg};
 use crate::prelude::*;
+use crate::readiness::Readiness;
+use crate::reload::Swappable;
+use crate::subscriptions::Subscriptions;
+use crate::tracker::Tracker;
+use crate::webhook::WebhookMonitor;
 
+type Router = CommandRouter<BotContext>;
+
+/// Context of the command handlers.
+#[derive(Clone)]
+pub struct BotContext {
+    pub bot_api: BotApi,
+
+    /// Reads the cluster status for `/status`.
+    pub redis: Redis,
+
+    pub subscriptions: Subscriptions,
+    pub live_locations: LiveLocations,
+    pub tracker: Tracker,
+
+    /// Notification settings, the battery ones are also used by `/battery`.
+    pub opts: Arc<Swappable<NotificationOpts>>,
+
+    pub catalog: Arc<Catalog>,
+}
+
+impl AsRef<BotApi> for BotContext {
+    fn as_ref(&self) -> &BotApi {
+        &self.bot_api
+    }
+}
+
+/// Renders the replies in the chat's locale.
+pub trait Localize {
+    fn catalog(&self) -> &Catalog;
+
+    /// The chat locale, if one is set.
+    fn chat_locale(&self, chat_id: i64) -> BoxFuture<'_, Result<Option<String>>>;
+}
+
+impl Localize for BotContext {
+    fn catalog(&self) -> &Catalog {
+        &self.catalog
+    }
+
+    fn chat_locale(&self, chat_id: i64) -> BoxFuture<'_, Result<Option<String>>> {
+        self.subscriptions.chat_locale(chat_id).boxed()
+    }
+}
+
+impl ReplyInvalidArgs for BotContext {
+    fn reply_invalid_args(
+        self,
+        message: models::Message,
+        command: &'static str,
+        error: Error,
+    ) -> BoxFuture<'static, Result<()>> {
+        async move { reply_invalid_args(&self, &message, command, &error).await }.boxed()
+    }
+}
+
+/// Run the bot until the shutdown is requested.
 pub async fn run(
-    api: BotApi,
+    context: BotContext,
+    readiness: Arc<Readiness>,
+    webhook_monitor: Arc<WebhookMonitor>,
+    bot_username: Option<String>,
     bind_endpoint: String,
-    webhook_url: String,
     secret_token: SecUtf8,
+    shutdown: &Shutdown,
 ) -> Result<()> {
     info!("setting up the bot…");
-    methods::SetMyCommands::default()
-        .command(models::BotCommand {
-            command: Cow::Borrowed("start"),
-            description: Cow::Borrowed("Tells your chat ID"),
-        })
-        .call(&api)
-        .await?;
-    methods::SetWebhook::new(webhook_url)
+    let api = context.bot_api.clone();
+    let router = Router::new(bot_username)
+        .command("start", "Tells your chat ID", on_start)
+        .command("subscribe", "Subscribes the chat to the updates", on_subscribe)
+        .command("unsubscribe", "Unsubscribes the chat from the updates", on_unsubscribe)
+        .command("where", "Shows the last known location", on_where)
+        .command("battery", "Shows the battery status", on_battery)
+        .command("language", "Shows or changes the chat language", on_language)
+        .command("status", "Shows the running instances", on_status)
+        .callback("where", |context, query| on_refresh(context, query, on_where))
+        .callback("battery", |context, query| on_refresh(context, query, on_battery));
+    router.set_my_commands().call(&api).await?;
+    if webhook_monitor.drop_pending_updates() {
+        warn!("dropping the pending updates…");
+    }
+    methods::SetWebhook::new(webhook_monitor.webhook_url().to_string())
         .allow_update(methods::AllowedUpdate::Message)
+        .allow_update(methods::AllowedUpdate::CallbackQuery)
         .secret_token(secret_token.unsecure())
+        .drop_pending_updates(webhook_monitor.drop_pending_updates())
         .call(&api)
         .await?;
 
     info!("running the bot…");
     let app = Route::new()
-        .at("/", post(post_update).head(get_health))
-        .at("/health", get(get_health).head(get_health))
-        .with(AddData::new(api))
+        .at("/", post(post_update).head(head_health))
+        .at("/health", get(get_health).head(head_health))
+        .at("/health/ready", get(get_ready))
+        .at("/metrics", get(rusty_shared_metrics::get_metrics))
+        .with(AddData::new(context))
+        .with(AddData::new(Arc::new(router)))
         .with(AddData::new(SecretToken(secret_token)))
+        .with(AddData::new(webhook_monitor.clone()))
+        .with(AddData::new(readiness))
         .with(TracingMiddleware);
-    Server::new(TcpListener::bind(bind_endpoint))
-        .run(app)
-        .await?;
+    // Bind before notifying, so that systemd doesn't consider the bot ready too early.
+    let acceptor = TcpListener::bind(bind_endpoint)
+        .into_acceptor()
+        .await
+        .context("failed to bind the web server")?;
+    systemd::notify_ready("serving the webhook");
+    let server_future = async {
+        Server::new_with_acceptor(acceptor)
+            .run_with_graceful_shutdown(app, shutdown.requested(), Some(shutdown.timeout()))
+            .await
+            .context("the web server has failed")
+    };
+    try_join(server_future, webhook_monitor.run(shutdown)).await?;
     Ok(())
 }
 
+#[derive(Serialize)]
+struct WebhookHealth {
+    /// The most recent `getWebhookInfo` result, if already available.
+    webhook: Option<models::WebhookInfo>,
+}
+
 #[handler]
 #[instrument(skip_all)]
-async fn get_health() -> StatusCode {
+async fn head_health() -> StatusCode {
     StatusCode::NO_CONTENT
 }
 
+/// Reports the webhook status, as Telegram sees it.
+#[handler]
+#[instrument(skip_all)]
+async fn get_health(webhook_monitor: Data<&Arc<WebhookMonitor>>) -> impl IntoResponse {
+    Json(WebhookHealth {
+        webhook: webhook_monitor.last_info(),
+    })
+}
+
+/// Checks the dependencies: responds with `200 OK` when the bot is working,
+/// and with `503 Service Unavailable` otherwise.
+#[handler]
+#[instrument(skip_all)]
+async fn get_ready(readiness: Data<&Arc<Readiness>>) -> impl IntoResponse {
+    let report = readiness.check().await;
+    let status = if report.is_ready {
+        StatusCode::OK
+    } else {
+        warn!(?report.redis.error, report.listener.age_secs, report.telegram.age_secs, "🙅 not ready");
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    Json(report).with_status(status)
+}
+
 #[handler]
 #[instrument(skip_all)]
 async fn post_update(
     TypedHeader(SecretToken(secret_token)): TypedHeader<SecretToken>,
     Json(update): Json<models::Update>,
-    bot_api: Data<&BotApi>,
+    context: Data<&BotContext>,
+    router: Data<&Arc<Router>>,
     expected_secret_token: Data<&SecretToken>,
 ) -> Result<StatusCode> {
     info!("👌 handling the update…");
@@ -70,7 +206,7 @@ async fn post_update(
         return Ok(StatusCode::UNAUTHORIZED);
     }
 
-    if let Err(error) = handle_update(update, &bot_api).await {
+    if let Err(error) = handle_update(update, context.0, router.0).await {
         error!("failed to handle the update: {:#}", error);
     }
 
@@ -78,16 +214,22 @@ async fn post_update(
 }
 
 #[instrument(skip_all, fields(update.id = update.id))]
-async fn handle_update(update: models::Update, bot_api: &BotApi) -> Result<()> {
+async fn handle_update<C: AsRef<BotApi> + Clone>(
+    update: models::Update,
+    context: &C,
+    router: &CommandRouter<C>,
+) -> Result<()> {
     match update.payload {
-        models::UpdatePayload::Message(message) => match &message.text {
-            Some(text) if text.starts_with("/start") => {
-                on_start(bot_api, message).await?;
+        models::UpdatePayload::Message(message) => {
+            if !router.dispatch(context.clone(), message).await? {
+                debug!("ignoring the unsupported message");
             }
-            _ => {
-                debug!(?message.text, "ignoring the unsupported message");
+        }
+        models::UpdatePayload::CallbackQuery(query) => {
+            if !router.dispatch_callback(context.clone(), query).await? {
+                debug!("ignoring the unsupported callback query");
             }
-        },
+        }
         payload => {
             debug!(?payload, "ignoring the unsupported update");
         }
@@ -96,14 +238,533 @@ async fn handle_update(update: models::Update, bot_api: &BotApi) -> Result<()> {
 }
 
 #[instrument(skip_all, fields(message.id = message.id))]
-async fn on_start(bot_api: &BotApi, message: models::Message) -> Result<()> {
-    methods::SendMessage::new(
-        message.chat.id,
-        format!(r#"👋 Your chat ID is `{}`\."#, message.chat.id),
+async fn on_start<C: AsRef<BotApi> + Localize>(
+    context: C,
+    message: models::Message,
+    _args: (),
+) -> Result<()> {
+    let values = HashMap::from([("chat_id", message.chat.id.to_string())]);
+    reply(&context, &message, "start", &values).await
+}
+
+#[instrument(skip_all, fields(message.id = message.id))]
+async fn on_subscribe(context: BotContext, message: models::Message, _args: ()) -> Result<()> {
+    let key = if !is_sent_by_allowed_user(&context, &message) {
+        "subscribe-not-allowed"
+    } else if context.subscriptions.subscribe(message.chat.id).await? {
+        info!(message.chat.id, "✅ subscribed");
+        "subscribed"
+    } else {
+        "already-subscribed"
+    };
+    reply(&context, &message, key, &HashMap::new()).await
+}
+
+#[instrument(skip_all, fields(message.id = message.id))]
+async fn on_unsubscribe(context: BotContext, message: models::Message, _args: ()) -> Result<()> {
+    let key = if !is_sent_by_allowed_user(&context, &message) {
+        "unsubscribe-not-allowed"
+    } else if context.subscriptions.unsubscribe(message.chat.id).await? {
+        info!(message.chat.id, "👋 unsubscribed");
+        if let Err(error) = context.live_locations.remove(message.chat.id).await {
+            error!(message.chat.id, "failed to remove the live location: {:#}", error);
+        }
+        "unsubscribed"
+    } else {
+        "already-unsubscribed"
+    };
+    reply(&context, &message, key, &HashMap::new()).await
+}
+
+#[instrument(skip_all, fields(message.id = message.id))]
+async fn on_where(context: BotContext, message: models::Message, _args: ()) -> Result<()> {
+    if !context.subscriptions.is_subscribed(message.chat.id).await? {
+        return reply(&context, &message, "not-subscribed", &HashMap::new()).await;
+    }
+    let position = match context.tracker.last_position().await? {
+        Some(position) => position,
+        None => return reply(&context, &message, "no-position", &HashMap::new()).await,
+    };
+    let location = methods::Location::new(
+        models::ChatId::UniqueId(message.chat.id),
+        position.latitude,
+        position.longitude,
     )
-    .parse_mode(models::ParseMode::MarkdownV2)
-    .reply_to_message_id(message.id)
-    .call(bot_api)
-    .await?;
+    .horizontal_accuracy(position.accuracy as f32)
+    .heading(position.course);
+    methods::SendLocation::new(location)
+        .call(&context.bot_api)
+        .await?;
+
+    let values = HashMap::from([
+        ("age", format_duration(now() - position.timestamp.timestamp())),
+        ("accuracy", position.accuracy.to_string()),
+    ]);
+    let text = localize(&context, message.chat.id, "position", &values).await?;
+    methods::SendMessage::new(message.chat.id, text)
+        .parse_mode(TemplateArg::PARSE_MODE)
+        .reply_markup(refresh_keyboard(&context, message.chat.id, "where").await?)
+        .call(&context.bot_api)
+        .await?;
     Ok(())
 }
+
+#[instrument(skip_all, fields(message.id = message.id))]
+async fn on_battery(context: BotContext, message: models::Message, _args: ()) -> Result<()> {
+    if !context.subscriptions.is_subscribed(message.chat.id).await? {
+        return reply(&context, &message, "not-subscribed", &HashMap::new()).await;
+    }
+    let history = context
+        .tracker
+        .hardware_history(Tracker::HARDWARE_HISTORY_COUNT)
+        .await?;
+    let last_entry = match history.first() {
+        Some(entry) => entry,
+        None => return reply(&context, &message, "no-battery-data", &HashMap::new()).await,
+    };
+
+    let opts = context.opts.load();
+    let locale = context.subscriptions.chat_locale(message.chat.id).await?;
+    let text = |key: &str, values: &[(&str, String)]| {
+        let values = values.iter().cloned().collect();
+        context.catalog.text(locale.as_deref(), key, &values)
+    };
+    let charging_state = match last_entry.is_charging {
+        Some(true) => text("charging-text", &[])?,
+        Some(false) => text("not-charging-text", &[])?,
+        None => text("unknown-charging-text", &[])?,
+    };
+    let last_full_charge = match history
+        .iter()
+        .find(|entry| entry.battery_level >= opts.battery.full_level)
+    {
+        Some(entry) if entry.timestamp == last_entry.timestamp => {
+            text("full-charge-now-text", &[])?
+        }
+        Some(entry) => {
+            let age = format_duration(now() - entry.timestamp.timestamp());
+            text("full-charge-ago-text", &[("age", age)])?
+        }
+        None => text("no-full-charge-text", &[("count", history.len().to_string())])?,
+    };
+    let drain = match DrainEstimate::from_history(&history, opts.battery.drain_window_secs) {
+        Some(DrainEstimate {
+            drain_per_hour,
+            hours_left: Some(hours_left),
+        }) => text(
+            "drain-text",
+            &[
+                ("drain_per_hour", format!("{:.1}", drain_per_hour)),
+                ("time_left", format_duration((hours_left * 3600.0) as i64)),
+            ],
+        )?,
+        Some(DrainEstimate {
+            hours_left: None, ..
+        }) => text("not-discharging-text", &[])?,
+        None => text("no-drain-text", &[])?,
+    };
+    let values = HashMap::from([
+        ("level", last_entry.battery_level.to_string()),
+        ("charging_state", charging_state),
+        ("age", format_duration(now() - last_entry.timestamp.timestamp())),
+        ("last_full_charge", last_full_charge),
+        ("drain", drain),
+    ]);
+    let text = context
+        .catalog
+        .render(locale.as_deref(), "battery", &values)?;
+    methods::SendMessage::new(message.chat.id, text)
+        .parse_mode(TemplateArg::PARSE_MODE)
+        .reply_to_message_id(message.id)
+        .reply_markup(refresh_keyboard(&context, message.chat.id, "battery").await?)
+        .call(&context.bot_api)
+        .await?;
+    Ok(())
+}
+
+/// Repeat the command, when its «Refresh» button is pressed.
+#[instrument(skip_all, fields(callback_query.id = query.id))]
+async fn on_refresh<F, Fut>(
+    context: BotContext,
+    query: models::CallbackQuery,
+    handler: F,
+) -> Result<()>
+where
+    F: FnOnce(BotContext, models::Message, ()) -> Fut,
+    Fut: Future<Output = Result<()>>,
+{
+    match query.message {
+        Some(message) => handler(context, message, ()).await,
+        None => {
+            // The message is too old, Telegram no longer sends it.
+            debug!("no message in the callback query");
+            Ok(())
+        }
+    }
+}
+
+#[instrument(skip_all, fields(message.id = message.id))]
+async fn on_language(
+    context: BotContext,
+    message: models::Message,
+    locale: Option<String>,
+) -> Result<()> {
+    let locales = context.catalog.locale_list();
+    let locale = match locale {
+        None => {
+            let locale = context.subscriptions.chat_locale(message.chat.id).await?;
+            let locale = context.catalog.resolve(locale.as_deref()).to_string();
+            let values = HashMap::from([("locale", locale), ("locales", locales)]);
+            return reply(&context, &message, "language", &values).await;
+        }
+        Some(locale) => locale.to_lowercase(),
+    };
+    if !is_sent_by_allowed_user(&context, &message) {
+        return reply(&context, &message, "language-not-allowed", &HashMap::new()).await;
+    }
+    if !context.catalog.has_locale(&locale) {
+        let values = HashMap::from([("locale", locale), ("locales", locales)]);
+        return reply(&context, &message, "unknown-language", &values).await;
+    }
+    context
+        .subscriptions
+        .set_chat_locale(message.chat.id, &locale)
+        .await?;
+    info!(message.chat.id, locale, "🌐 changed the chat locale");
+    let values = HashMap::from([("locale", locale)]);
+    reply(&context, &message, "language-changed", &values).await
+}
+
+#[instrument(skip_all, fields(message.id = message.id))]
+async fn on_status(context: BotContext, message: models::Message, _args: ()) -> Result<()> {
+    if !is_sent_by_allowed_user(&context, &message) {
+        return reply(&context, &message, "status-not-allowed", &HashMap::new()).await;
+    }
+    let instances = ServiceStatus::list(&context.redis).await?;
+    if instances.is_empty() {
+        return reply(&context, &message, "no-instances", &HashMap::new()).await;
+    }
+
+    let locale = context.subscriptions.chat_locale(message.chat.id).await?;
+    let now = now();
+    let lines = instances
+        .into_iter()
+        .map(|instance| {
+            let mut values = HashMap::from([
+                ("service", instance.service_name),
+                ("version", instance.version),
+                ("hostname", instance.hostname),
+                ("uptime", format_duration(now - instance.started_at)),
+            ]);
+            let key = match instance.last_activity_at {
+                Some(last_activity_at) => {
+                    values.insert("age", format_duration(now - last_activity_at));
+                    "instance-text"
+                }
+                None => "idle-instance-text",
+            };
+            let text = context.catalog.text(locale.as_deref(), key, &values)?;
+            if !instance.is_leader {
+                return Ok(text);
+            }
+            let values = HashMap::from([("instance", text)]);
+            context
+                .catalog
+                .text(locale.as_deref(), "leader-instance-text", &values)
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let values = HashMap::from([("instances", lines.join("\n"))]);
+    reply(&context, &message, "status", &values).await
+}
+
+/// Format the duration in seconds with at most two units, for example: `2h 5m`.
+fn format_duration(secs: i64) -> String {
+    let secs = secs.max(0);
+    let (days, hours, minutes) = (secs / 86400, secs % 86400 / 3600, secs % 3600 / 60);
+    if days != 0 {
+        format!("{}d {}h", days, hours)
+    } else if hours != 0 {
+        format!("{}h {}m", hours, minutes)
+    } else if minutes != 0 {
+        format!("{}m", minutes)
+    } else {
+        format!("{}s", secs)
+    }
+}
+
+/// Inline keyboard with the «Refresh» button, which sends the callback data back.
+async fn refresh_keyboard<C: Localize>(
+    context: &C,
+    chat_id: i64,
+    callback_data: &str,
+) -> Result<models::InlineKeyboardMarkup> {
+    let locale = context.chat_locale(chat_id).await?;
+    let text = context
+        .catalog()
+        .text(locale.as_deref(), "refresh-button-text", &HashMap::new())?;
+    let button = models::InlineKeyboardButton::callback_data(text, callback_data);
+    Ok(models::InlineKeyboardMarkup::default().row([button]))
+}
+
+fn is_sent_by_allowed_user(context: &BotContext, message: &models::Message) -> bool {
+    let is_allowed = message
+        .from
+        .as_ref()
+        .is_some_and(|user| context.subscriptions.is_allowed(user.id));
+    if !is_allowed {
+        warn!(?message.from, "🙅 the user is not allowed");
+    }
+    is_allowed
+}
+
+/// Render the catalog message in the chat's locale.
+async fn localize<C: Localize>(
+    context: &C,
+    chat_id: i64,
+    key: &str,
+    values: &HashMap<&str, String>,
+) -> Result<String> {
+    let locale = context.chat_locale(chat_id).await?;
+    context.catalog().render(locale.as_deref(), key, values)
+}
+
+/// Reply with the argument parsing error.
+async fn reply_invalid_args<C: AsRef<BotApi> + Localize>(
+    context: &C,
+    message: &models::Message,
+    command: &str,
+    error: &Error,
+) -> Result<()> {
+    let values = HashMap::from([
+        ("command", command.to_string()),
+        ("error", format!("{:#}", error)),
+    ]);
+    reply(context, message, "invalid-args", &values).await
+}
+
+/// Reply with the catalog message.
+async fn reply<C: AsRef<BotApi> + Localize>(
+    context: &C,
+    message: &models::Message,
+    key: &str,
+    values: &HashMap<&str, String>,
+) -> Result<()> {
+    let text = localize(context, message.chat.id, key, values).await?;
+    methods::SendMessage::new(message.chat.id, text)
+        .parse_mode(TemplateArg::PARSE_MODE)
+        .reply_to_message_id(message.id)
+        .call(context.as_ref())
+        .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use rusty_shared_telegram::mock::MockServer;
+    use serde_json::{from_value, json};
+
+    use super::*;
+
+    #[derive(Clone)]
+    struct TestContext {
+        bot_api: BotApi,
+        catalog: Arc<Catalog>,
+    }
+
+    impl TestContext {
+        fn new(server: &MockServer) -> Result<Self> {
+            Ok(Self {
+                bot_api: server.bot_api()?,
+                catalog: Arc::new(Catalog::new("en", None)?),
+            })
+        }
+    }
+
+    impl AsRef<BotApi> for TestContext {
+        fn as_ref(&self) -> &BotApi {
+            &self.bot_api
+        }
+    }
+
+    impl Localize for TestContext {
+        fn catalog(&self) -> &Catalog {
+            &self.catalog
+        }
+
+        fn chat_locale(&self, _chat_id: i64) -> BoxFuture<'_, Result<Option<String>>> {
+            async { Ok(None) }.boxed()
+        }
+    }
+
+    impl ReplyInvalidArgs for TestContext {
+        fn reply_invalid_args(
+            self,
+            message: models::Message,
+            command: &'static str,
+            error: Error,
+        ) -> BoxFuture<'static, Result<()>> {
+            async move { reply_invalid_args(&self, &message, command, &error).await }.boxed()
+        }
+    }
+
+    #[async_std::test]
+    async fn start_ok() -> Result<()> {
+        let server = MockServer::start().await?;
+        let context = TestContext::new(&server)?;
+        let router = CommandRouter::new(Some("RustyBot".to_string())).command(
+            "start",
+            "Tells your chat ID",
+            on_start,
+        );
+        server.respond("sendMessage", json!({"message_id": 2, "chat": {"id": 100}}));
+
+        let update = from_value(json!({
+            "update_id": 1,
+            "message": {
+                "message_id": 1,
+                "chat": {"id": 100},
+                "text": "/start@RustyBot",
+                "entities": [{"type": "bot_command", "offset": 0, "length": 15}],
+            },
+        }))?;
+        handle_update(update, &context, &router).await?;
+
+        let calls = server.calls_of("sendMessage");
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].params["chat_id"], 100);
+        assert_eq!(calls[0].params["reply_to_message_id"], 1);
+        assert_eq!(calls[0].params["parse_mode"], "MarkdownV2");
+        assert_eq!(calls[0].params["text"], r#"👋 Your chat ID is `100`\."#);
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn start_other_bot_ignored_ok() -> Result<()> {
+        let server = MockServer::start().await?;
+        let context = TestContext::new(&server)?;
+        let router = CommandRouter::new(Some("RustyBot".to_string())).command(
+            "start",
+            "Tells your chat ID",
+            on_start,
+        );
+
+        let update = from_value(json!({
+            "update_id": 1,
+            "message": {
+                "message_id": 1,
+                "chat": {"id": 100},
+                "text": "/start@OtherBot",
+                "entities": [{"type": "bot_command", "offset": 0, "length": 15}],
+            },
+        }))?;
+        handle_update(update, &context, &router).await?;
+
+        assert!(server.calls().is_empty());
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn invalid_args_ok() -> Result<()> {
+        let server = MockServer::start().await?;
+        let context = TestContext::new(&server)?;
+        let router = CommandRouter::new(Some("RustyBot".to_string())).command(
+            "beep",
+            "Beeps the specified number of times",
+            |_context, _message, _times: u8| async { Ok(()) },
+        );
+        server.respond("sendMessage", json!({"message_id": 2, "chat": {"id": 100}}));
+
+        let update = from_value(json!({
+            "update_id": 1,
+            "message": {
+                "message_id": 1,
+                "chat": {"id": 100},
+                "text": "/beep often",
+                "entities": [{"type": "bot_command", "offset": 0, "length": 5}],
+            },
+        }))?;
+        handle_update(update, &context, &router).await?;
+
+        let calls = server.calls_of("sendMessage");
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].params["reply_to_message_id"], 1);
+        assert_eq!(
+            calls[0].params["text"],
+            r#"🤔 Invalid arguments for /beep: \`often\` is not a valid number: invalid digit found in string"#,
+        );
+        Ok(())
+    }
+
+    fn callback_query_update(data: &str) -> Result<models::Update> {
+        Ok(from_value(json!({
+            "update_id": 1,
+            "callback_query": {
+                "id": "42",
+                "from": {"id": 7, "first_name": "Alice"},
+                "message": {"message_id": 2, "chat": {"id": 100}, "text": "🔋 42%"},
+                "chat_instance": "-1",
+                "data": data,
+            },
+        }))?)
+    }
+
+    #[async_std::test]
+    async fn callback_query_ok() -> Result<()> {
+        let server = MockServer::start().await?;
+        let context = TestContext::new(&server)?;
+        let router = CommandRouter::new(Some("RustyBot".to_string())).callback(
+            "start",
+            |context, query: models::CallbackQuery| async move {
+                on_start(context, query.message.context("no message")?, ()).await
+            },
+        );
+        server
+            .respond("answerCallbackQuery", json!(true))
+            .respond("sendMessage", json!({"message_id": 3, "chat": {"id": 100}}));
+
+        handle_update(callback_query_update("start")?, &context, &router).await?;
+
+        let calls = server.calls_of("answerCallbackQuery");
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].params["callback_query_id"], "42");
+        let calls = server.calls_of("sendMessage");
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].params["reply_to_message_id"], 2);
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn unknown_callback_query_answered_ok() -> Result<()> {
+        let server = MockServer::start().await?;
+        let context = TestContext::new(&server)?;
+        let router = CommandRouter::<TestContext>::new(None);
+        server.respond("answerCallbackQuery", json!(true));
+
+        handle_update(callback_query_update("beep")?, &context, &router).await?;
+
+        assert_eq!(server.calls_of("answerCallbackQuery").len(), 1);
+        assert!(server.calls_of("sendMessage").is_empty());
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn refresh_keyboard_ok() -> Result<()> {
+        let server = MockServer::start().await?;
+        let context = TestContext::new(&server)?;
+        let keyboard = refresh_keyboard(&context, 100, "battery").await?;
+        assert_eq!(
+            serde_json::to_value(keyboard)?,
+            json!({"inline_keyboard": [[{"text": "🔄 Refresh", "callback_data": "battery"}]]}),
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn format_duration_ok() {
+        assert_eq!(format_duration(-5), "0s");
+        assert_eq!(format_duration(42), "42s");
+        assert_eq!(format_duration(300), "5m");
+        assert_eq!(format_duration(7500), "2h 5m");
+        assert_eq!(format_duration(3 * 86400 + 4 * 3600 + 59), "3d 4h");
+    }
+}
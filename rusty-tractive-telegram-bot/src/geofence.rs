@@ -0,0 +1,458 @@
+//! Named zones and enter/leave transition tracking.
+
+use std::collections::HashSet;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+use anyhow::{anyhow, bail, ensure, Context, Error, Result};
+
+/// Mean Earth radius in meters.
+const EARTH_RADIUS: f64 = 6_371_000.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Point {
+    pub latitude: f64,
+    pub longitude: f64,
+}
+
+impl Point {
+    pub const fn new(latitude: f64, longitude: f64) -> Self {
+        Self {
+            latitude,
+            longitude,
+        }
+    }
+
+    /// Great-circle distance in meters.
+    pub fn distance_to(self, other: Self) -> f64 {
+        let (lat_1, lat_2) = (self.latitude.to_radians(), other.latitude.to_radians());
+        let d_lat = lat_2 - lat_1;
+        let d_lon = (other.longitude - self.longitude).to_radians();
+        let a =
+            (d_lat / 2.0).sin().powi(2) + lat_1.cos() * lat_2.cos() * (d_lon / 2.0).sin().powi(2);
+        2.0 * EARTH_RADIUS * a.sqrt().asin()
+    }
+
+    /// Project the point onto the local plane centered at `origin`, in meters.
+    fn project(self, origin: Self) -> (f64, f64) {
+        let x = (self.longitude - origin.longitude).to_radians()
+            * origin.latitude.to_radians().cos()
+            * EARTH_RADIUS;
+        let y = (self.latitude - origin.latitude).to_radians() * EARTH_RADIUS;
+        (x, y)
+    }
+}
+
+impl FromStr for Point {
+    type Err = Error;
+
+    /// Parse `<latitude>,<longitude>`.
+    fn from_str(s: &str) -> Result<Self> {
+        let (latitude, longitude) = s
+            .split_once(',')
+            .ok_or_else(|| anyhow!("expected `<latitude>,<longitude>`, got `{}`", s))?;
+        let (latitude, longitude): (f64, f64) =
+            (latitude.trim().parse()?, longitude.trim().parse()?);
+        ensure!(
+            (-90.0..=90.0).contains(&latitude),
+            "latitude must be between -90 and 90, got `{}`",
+            latitude,
+        );
+        ensure!(
+            (-180.0..=180.0).contains(&longitude),
+            "longitude must be between -180 and 180, got `{}`",
+            longitude,
+        );
+        Ok(Self::new(latitude, longitude))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Shape {
+    Circle { center: Point, radius: f64 },
+    Polygon(Vec<Point>),
+}
+
+impl Shape {
+    /// Signed distance from the point to the shape boundary in meters.
+    /// It's negative inside the shape.
+    pub fn signed_distance(&self, point: Point) -> f64 {
+        match self {
+            Self::Circle { center, radius } => center.distance_to(point) - radius,
+            Self::Polygon(vertices) => {
+                let projected: Vec<(f64, f64)> = vertices
+                    .iter()
+                    .map(|vertex| vertex.project(point))
+                    .collect();
+                let distance = projected
+                    .iter()
+                    .zip(projected.iter().cycle().skip(1))
+                    .map(|(start, end)| distance_to_segment(*start, *end))
+                    .fold(f64::INFINITY, f64::min);
+                if contains_origin(&projected) {
+                    -distance
+                } else {
+                    distance
+                }
+            }
+        }
+    }
+}
+
+/// Distance from the origin to the segment.
+fn distance_to_segment((x_1, y_1): (f64, f64), (x_2, y_2): (f64, f64)) -> f64 {
+    let (dx, dy) = (x_2 - x_1, y_2 - y_1);
+    let length_squared = dx * dx + dy * dy;
+    let t = if length_squared == 0.0 {
+        0.0
+    } else {
+        (-(x_1 * dx + y_1 * dy) / length_squared).clamp(0.0, 1.0)
+    };
+    (x_1 + t * dx).hypot(y_1 + t * dy)
+}
+
+/// Ray casting test for the origin.
+fn contains_origin(vertices: &[(f64, f64)]) -> bool {
+    let mut is_inside = false;
+    for ((x_1, y_1), (x_2, y_2)) in vertices.iter().zip(vertices.iter().cycle().skip(1)) {
+        if (*y_1 > 0.0) != (*y_2 > 0.0) && 0.0 < x_1 + (x_2 - x_1) * -y_1 / (y_2 - y_1) {
+            is_inside = !is_inside;
+        }
+    }
+    is_inside
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ZoneKind {
+    /// Leaving the zone triggers a notification.
+    Home,
+
+    /// Entering the zone triggers a notification.
+    Danger,
+}
+
+/// Named zone.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Zone {
+    pub kind: ZoneKind,
+    pub name: String,
+    pub shape: Shape,
+}
+
+impl FromStr for Zone {
+    type Err = Error;
+
+    /// Parse `<kind>:<name>=<geometry>`, where `kind` is either `home` or `danger`,
+    /// and `geometry` is either `<latitude>,<longitude>,<radius>`
+    /// or at least three `<latitude>,<longitude>` polygon vertices separated with `;`.
+    fn from_str(s: &str) -> Result<Self> {
+        let (kind, rest) = s
+            .split_once(':')
+            .ok_or_else(|| anyhow!("expected `<kind>:<name>=<geometry>`, got `{}`", s))?;
+        let kind = match kind.trim() {
+            "home" => ZoneKind::Home,
+            "danger" => ZoneKind::Danger,
+            kind => bail!("unknown zone kind `{}`, expected `home` or `danger`", kind),
+        };
+        let (name, geometry) = rest
+            .split_once('=')
+            .ok_or_else(|| anyhow!("expected `<name>=<geometry>`, got `{}`", rest))?;
+        let name = name.trim();
+        ensure!(!name.is_empty(), "zone name must not be empty");
+
+        let shape = if geometry.contains(';') {
+            let vertices = geometry
+                .split(';')
+                .map(Point::from_str)
+                .collect::<Result<Vec<_>>>()
+                .with_context(|| format!("invalid polygon of zone `{}`", name))?;
+            ensure!(vertices.len() >= 3, "polygon of zone `{}` needs at least 3 vertices", name);
+            Shape::Polygon(vertices)
+        } else {
+            let (center, radius) = geometry
+                .rsplit_once(',')
+                .ok_or_else(|| anyhow!("expected `<latitude>,<longitude>,<radius>`"))?;
+            let radius: f64 = radius
+                .trim()
+                .parse()
+                .with_context(|| format!("invalid radius of zone `{}`", name))?;
+            ensure!(radius > 0.0, "radius of zone `{}` must be positive", name);
+            Shape::Circle {
+                center: center
+                    .parse()
+                    .with_context(|| format!("invalid center of zone `{}`", name))?,
+                radius,
+            }
+        };
+
+        Ok(Self {
+            kind,
+            name: name.to_string(),
+            shape,
+        })
+    }
+}
+
+/// Make sure that the zone names are unique, because the zone states are stored by name.
+pub fn ensure_unique_names(zones: &[Zone]) -> Result<()> {
+    let mut names = HashSet::with_capacity(zones.len());
+    for zone in zones {
+        ensure!(names.insert(zone.name.as_str()), "duplicate zone name `{}`", zone.name);
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Transition {
+    Entered,
+    Left,
+}
+
+/// Persisted zone state.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ZoneState {
+    pub is_inside: bool,
+
+    /// Timestamp since which the positions consistently point to the opposite state.
+    pub pending_since: Option<i64>,
+}
+
+impl Display for ZoneState {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:", u8::from(self.is_inside))?;
+        if let Some(pending_since) = self.pending_since {
+            write!(f, "{}", pending_since)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for ZoneState {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (is_inside, pending_since) = s
+            .split_once(':')
+            .ok_or_else(|| anyhow!("invalid zone state: `{}`", s))?;
+        Ok(Self {
+            is_inside: is_inside == "1",
+            pending_since: match pending_since {
+                "" => None,
+                pending_since => Some(pending_since.parse()?),
+            },
+        })
+    }
+}
+
+/// Absorbs the GPS jitter around the zone boundaries.
+#[derive(Debug, Clone, Copy)]
+pub struct Debounce {
+    /// Distance beyond the boundary, in meters, required to change the state.
+    /// The position accuracy is used instead when it's worse.
+    pub hysteresis: f64,
+
+    /// Minimal time, in seconds, during which the positions must consistently
+    /// point to the new state.
+    pub min_dwell_time: i64,
+}
+
+impl Zone {
+    /// Update the zone state with the new position.
+    ///
+    /// Returns the new state and the transition, if any.
+    /// The very first position only initializes the state.
+    pub fn update(
+        &self,
+        state: Option<ZoneState>,
+        point: Point,
+        accuracy: f64,
+        timestamp: i64,
+        debounce: Debounce,
+    ) -> (ZoneState, Option<Transition>) {
+        let signed_distance = self.shape.signed_distance(point);
+        let state = match state {
+            Some(state) => state,
+            None => {
+                let state = ZoneState {
+                    is_inside: signed_distance <= 0.0,
+                    pending_since: None,
+                };
+                return (state, None);
+            }
+        };
+
+        let margin = debounce.hysteresis.max(accuracy);
+        let is_beyond_margin = if state.is_inside {
+            signed_distance > margin
+        } else {
+            signed_distance < -margin
+        };
+        if !is_beyond_margin {
+            let state = ZoneState {
+                pending_since: None,
+                ..state
+            };
+            return (state, None);
+        }
+
+        let pending_since = state.pending_since.unwrap_or(timestamp);
+        if timestamp - pending_since < debounce.min_dwell_time {
+            let state = ZoneState {
+                pending_since: Some(pending_since),
+                ..state
+            };
+            return (state, None);
+        }
+
+        let state = ZoneState {
+            is_inside: !state.is_inside,
+            pending_since: None,
+        };
+        let transition = if state.is_inside {
+            Transition::Entered
+        } else {
+            Transition::Left
+        };
+        (state, Some(transition))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DEBOUNCE: Debounce = Debounce {
+        hysteresis: 25.0,
+        min_dwell_time: 60,
+    };
+
+    #[test]
+    fn parse_circle_ok() -> Result<()> {
+        let zone: Zone = "home:Home=52.37, 4.89, 150".parse()?;
+        assert_eq!(zone.kind, ZoneKind::Home);
+        assert_eq!(zone.name, "Home");
+        assert_eq!(
+            zone.shape,
+            Shape::Circle {
+                center: Point::new(52.37, 4.89),
+                radius: 150.0,
+            }
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn parse_polygon_ok() -> Result<()> {
+        let zone: Zone = "danger:Road=52.0,4.0;52.0,4.1;52.1,4.1".parse()?;
+        assert_eq!(zone.kind, ZoneKind::Danger);
+        assert!(matches!(zone.shape, Shape::Polygon(vertices) if vertices.len() == 3));
+        Ok(())
+    }
+
+    #[test]
+    fn parse_invalid_err() {
+        assert!("park:Park=52.0,4.0,10".parse::<Zone>().is_err());
+        assert!("home:Home=52.0,4.0".parse::<Zone>().is_err());
+        assert!("home:Home=52.0,4.0,-10".parse::<Zone>().is_err());
+        assert!("danger:Road=52.0,4.0;52.0,4.1".parse::<Zone>().is_err());
+        assert!("home:Home=91.0,4.0,10".parse::<Zone>().is_err());
+        assert!("danger:Road=52.0,4.0;52.0,181.0;52.1,4.1"
+            .parse::<Zone>()
+            .is_err());
+    }
+
+    #[test]
+    fn ensure_unique_names_ok() -> Result<()> {
+        let home: Zone = "home:Home=52.0,4.0,10".parse()?;
+        let road: Zone = "danger:Road=52.0,4.0,10".parse()?;
+        ensure_unique_names(&[home.clone(), road])?;
+        assert!(ensure_unique_names(&[home.clone(), home]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn circle_signed_distance_ok() {
+        let shape = Shape::Circle {
+            center: Point::new(52.0, 4.0),
+            radius: 100.0,
+        };
+        assert!((shape.signed_distance(Point::new(52.0, 4.0)) + 100.0).abs() < 0.001);
+        // One thousandth of a degree of latitude is about 111 meters.
+        assert!((shape.signed_distance(Point::new(52.001, 4.0)) - 11.19).abs() < 0.1);
+    }
+
+    #[test]
+    fn polygon_signed_distance_ok() {
+        let shape = Shape::Polygon(vec![
+            Point::new(0.0, 0.0),
+            Point::new(0.0, 0.002),
+            Point::new(0.002, 0.002),
+            Point::new(0.002, 0.0),
+        ]);
+        assert!((shape.signed_distance(Point::new(0.001, 0.001)) + 111.19).abs() < 0.1);
+        assert!((shape.signed_distance(Point::new(0.001, 0.003)) - 111.19).abs() < 0.1);
+        assert!((shape.signed_distance(Point::new(0.003, 0.003)) - 157.25).abs() < 0.1);
+    }
+
+    #[test]
+    fn zone_state_roundtrip_ok() -> Result<()> {
+        for state in [
+            ZoneState {
+                is_inside: true,
+                pending_since: None,
+            },
+            ZoneState {
+                is_inside: false,
+                pending_since: Some(1650802621),
+            },
+        ] {
+            assert_eq!(state.to_string().parse::<ZoneState>()?, state);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn update_ok() {
+        let zone: Zone = "home:Home=0.0,0.0,100".parse().unwrap();
+        let inside = Point::new(0.0, 0.0);
+        let boundary = Point::new(0.001, 0.0); // ~111 meters
+        let outside = Point::new(0.002, 0.0); // ~222 meters
+
+        let (state, transition) = zone.update(None, inside, 10.0, 0, DEBOUNCE);
+        assert!(state.is_inside);
+        assert_eq!(transition, None);
+
+        // Jitter within the hysteresis is ignored.
+        let (state, transition) = zone.update(Some(state), boundary, 10.0, 10, DEBOUNCE);
+        assert_eq!(state.pending_since, None);
+        assert_eq!(transition, None);
+
+        // Leaving is pending until the dwell time passes.
+        let (state, transition) = zone.update(Some(state), outside, 10.0, 20, DEBOUNCE);
+        assert_eq!(state.pending_since, Some(20));
+        assert_eq!(transition, None);
+        let (state, transition) = zone.update(Some(state), outside, 10.0, 80, DEBOUNCE);
+        assert!(!state.is_inside);
+        assert_eq!(transition, Some(Transition::Left));
+
+        // Returning briefly resets the pending transition.
+        let (state, _) = zone.update(Some(state), inside, 10.0, 90, DEBOUNCE);
+        assert_eq!(state.pending_since, Some(90));
+        let (state, _) = zone.update(Some(state), outside, 10.0, 100, DEBOUNCE);
+        assert_eq!(state.pending_since, None);
+        assert!(!state.is_inside);
+    }
+
+    #[test]
+    fn update_poor_accuracy_ok() {
+        let zone: Zone = "danger:Road=0.0,0.0,100".parse().unwrap();
+        let state = ZoneState {
+            is_inside: false,
+            pending_since: None,
+        };
+        let (state, transition) =
+            zone.update(Some(state), Point::new(0.0, 0.0), 150.0, 0, DEBOUNCE);
+        assert_eq!(state.pending_since, None);
+        assert_eq!(transition, None);
+    }
+}
@@ -1,29 +1,55 @@
 //! Implements Redis stream listener.
 
 use std::collections::HashMap;
+use std::sync::Arc;
 use std::time;
 
+use anyhow::bail;
+use async_std::future::timeout;
+use chrono::Utc;
 use fred::prelude::*;
 use fred::types::{RedisKey, XReadResponse, XID};
 use gethostname::gethostname;
-use rusty_shared_opts::heartbeat::Heartbeat;
+use rusty_shared_heartbeat::Heartbeat;
+use rusty_shared_opts::shutdown::Shutdown;
+use rusty_shared_opts::systemd;
 use rusty_shared_redis::Redis;
 use rusty_shared_telegram::api::BotApi;
 use rusty_shared_telegram::methods::*;
 use rusty_shared_telegram::models::*;
+use rusty_shared_time::now;
+use rusty_shared_tracing::propagation::set_parent;
 use rusty_shared_tractive::*;
+use serde::{Deserialize, Serialize};
+use tracing::{info_span, Instrument};
 
-use crate::opts::BatteryOpts;
+use crate::battery::DrainEstimate;
+use crate::bot::BotContext;
+use crate::geofence::{Point, Transition, ZoneKind, ZoneState};
+use crate::live_location::LiveLocations;
+use crate::locale::Catalog;
+use crate::opts::{NotificationOpts, TemplateArg};
 use crate::prelude::*;
+use crate::quiet_hours::{Delivery, Severity};
+use crate::readiness::Readiness;
+use crate::reload::Swappable;
+use crate::subscriptions::Subscriptions;
+use crate::tracker::Tracker;
 
 pub struct Listener {
     redis: Redis,
     bot_api: BotApi,
     heartbeat: Heartbeat,
-    battery_opts: BatteryOpts,
+    opts: Arc<Swappable<NotificationOpts>>,
+    notifier: Notifier,
+    readiness: Arc<Readiness>,
 
-    /// Target chat to which the updates will be posted.
-    chat_id: ChatId,
+    /// Chats to which the updates will be posted.
+    subscriptions: Subscriptions,
+
+    live_locations: LiveLocations,
+
+    tracker: Tracker,
 
     /// Consumer name within the Redis group.
     consumer_name: String,
@@ -41,28 +67,101 @@ struct RedisKeys {
     /// Tractive hardware position stream.
     hardware_stream: RedisKey,
 
-    /// Stores the pinned message IDs, so that we can unpin them later.
-    pinned_message_ids: RedisKey,
+    last_known_battery_level: RedisKey,
 
-    /// Live location message ID, so that we can update it at any time.
-    live_location_message_id: RedisKey,
+    /// Hash of the zone states by zone name.
+    zone_states: RedisKey,
 
-    last_known_battery_level: RedisKey,
+    /// Unix time of the most recently received stream entry.
+    last_entry_at: RedisKey,
+
+    /// Exists while the stale tracker alert is active.
+    /// Whoever manages to set or delete it, sends the respective notification.
+    stale_alert: RedisKey,
+
+    /// Notifications which are waiting for the quiet hours to end.
+    delayed_notifications: RedisKey,
+}
+
+/// Notification, which gets rendered in each chat's locale.
+#[derive(Serialize, Deserialize)]
+struct Notification {
+    /// Message key in the catalog.
+    key: String,
+
+    values: HashMap<String, String>,
+}
+
+impl Notification {
+    fn new<const N: usize>(key: &str, values: [(&str, String); N]) -> Self {
+        Self {
+            key: key.to_string(),
+            values: values
+                .into_iter()
+                .map(|(name, value)| (name.to_string(), value))
+                .collect(),
+        }
+    }
+}
+
+/// Renders the notifications and sends them to the chats.
+struct Notifier {
+    bot_api: BotApi,
+    opts: Arc<Swappable<NotificationOpts>>,
+    catalog: Arc<Catalog>,
+}
+
+impl Notifier {
+    /// Render the notification with its configured template, or the catalog message
+    /// in the chat's locale, and send it.
+    async fn send(
+        &self,
+        chat_id: i64,
+        locale: Option<&str>,
+        notification: &Notification,
+        disable_notification: bool,
+    ) -> Result<()> {
+        let values = notification
+            .values
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.clone()))
+            .collect();
+        let text = match self.opts.load().template(&notification.key) {
+            Some(template) => template.render(&values)?,
+            None => self.catalog.render(locale, &notification.key, &values)?,
+        };
+        SendMessage::new(chat_id, text)
+            .parse_mode(TemplateArg::PARSE_MODE)
+            .disable_notification(disable_notification)
+            .call(&self.bot_api)
+            .await?;
+        Ok(())
+    }
 }
 
 impl Listener {
+    /// `XREADGROUP` blocks for this long, so that the listener gets to check the staleness.
+    const BLOCK_TIMEOUT: time::Duration = time::Duration::from_secs(10);
     const LIVE_PERIOD: time::Duration = time::Duration::from_secs(86400);
+    /// Additional timeout on top of the blocking one, to prevent the freezes.
+    const READ_TIMEOUT: time::Duration = time::Duration::from_secs(15);
+    /// The live location gets rotated this long before its live period ends,
+    /// because Telegram stops accepting the edits afterwards.
+    const RENEWAL_MARGIN: time::Duration = time::Duration::from_secs(3600);
+    /// Number of attempts to store the zone states, when they're concurrently updated.
+    const ZONE_UPDATE_ATTEMPTS: usize = 3;
 
+    /// Create the listener, which shares the Bot API, the subscriptions and the settings with the bot.
     pub async fn new(
         redis: Redis,
-        bot_api: BotApi,
         heartbeat: Heartbeat,
         bot_user_id: i64,
         tracker_id: &str,
-        chat_id: i64,
-        battery_opts: BatteryOpts,
+        context: &BotContext,
+        readiness: Arc<Readiness>,
     ) -> Result<Self> {
-        let group_name = format!("rusty:telegram:{}", bot_user_id);
+        let group_name = Self::group_name(bot_user_id);
+        let prefix = format!("rusty:tractive:{}:telegram:{}", tracker_id, bot_user_id);
 
         let position_stream_key = position_stream_key(tracker_id);
         redis
@@ -75,166 +174,427 @@ impl Listener {
             .await?;
 
         let this = Self {
+            tracker: Tracker::new(redis.clone(), tracker_id),
             redis,
-            bot_api,
+            bot_api: context.bot_api.clone(),
             heartbeat,
             group_name,
-            chat_id: ChatId::UniqueId(chat_id),
+            subscriptions: context.subscriptions.clone(),
+            live_locations: context.live_locations.clone(),
             consumer_name: gethostname().into_string().unwrap(),
-            battery_opts,
+            opts: context.opts.clone(),
+            notifier: Notifier {
+                bot_api: context.bot_api.clone(),
+                opts: context.opts.clone(),
+                catalog: context.catalog.clone(),
+            },
+            readiness,
             keys: RedisKeys {
                 position_stream: position_stream_key,
                 hardware_stream: hardware_stream_key,
-                live_location_message_id: RedisKey::from(format!(
-                    "rusty:tractive:{}:telegram:{}:live_location_message_id",
-                    tracker_id, bot_user_id,
-                )),
-                pinned_message_ids: RedisKey::from(format!(
-                    "rusty:tractive:{}:telegram:{}:pinned_message_ids",
-                    tracker_id, bot_user_id,
-                )),
                 last_known_battery_level: RedisKey::from(format!(
-                    "rusty:tractive:{}:telegram:{}:last_known_battery_level",
-                    tracker_id, bot_user_id,
+                    "{}:last_known_battery_level",
+                    prefix
                 )),
+                zone_states: RedisKey::from(format!("{}:zone_states", prefix)),
+                last_entry_at: RedisKey::from(format!("{}:last_entry_at", prefix)),
+                stale_alert: RedisKey::from(format!("{}:stale_alert", prefix)),
+                delayed_notifications: RedisKey::from(format!("{}:delayed_notifications", prefix)),
             },
         };
         Ok(this)
     }
 
-    pub async fn run(self) -> Result<()> {
+    /// Redis stream consumer group name of the bot.
+    pub fn group_name(bot_user_id: i64) -> String {
+        format!("rusty:telegram:{}", bot_user_id)
+    }
+
+    /// Run the listener until the shutdown is requested.
+    ///
+    /// The entries which have been read get handled before returning,
+    /// so the shutdown is delayed by up to the block timeout.
+    pub async fn run(self, shutdown: &Shutdown) -> Result<()> {
         info!("running the listener…");
-        loop {
-            self.handle_entries().await?;
-            self.heartbeat.send().await;
+        // Start counting the staleness from now, unless there's been an entry already.
+        self.redis
+            .pool
+            .set::<(), _, _>(&self.keys.last_entry_at, now(), None, Some(SetOptions::NX), false)
+            .await?;
+        while !shutdown.is_requested() {
+            if let Some(n_entries) = self.handle_entries().await? {
+                self.readiness.on_read();
+                // The heartbeat only goes out on new entries, but the listener is alive anyway.
+                systemd::notify_watchdog();
+                if n_entries != 0 {
+                    self.heartbeat.send().await;
+                }
+            }
+            self.check_staleness().await?;
+            self.send_delayed_notifications().await?;
         }
+        info!("the listener has stopped");
+        Ok(())
     }
 
-    async fn handle_entries(&self) -> Result<()> {
-        #[allow(clippy::mutable_key_type)]
-        let response: XReadResponse<RedisKey, String, String, String> = self
-            .redis
-            .pool
-            .xreadgroup_map(
+    /// Read and handle the new entries.
+    ///
+    /// Returns the number of the handled entries, or `None` if the read has timed out.
+    async fn handle_entries(&self) -> Result<Option<usize>> {
+        let response: RedisValue = match timeout(
+            Self::READ_TIMEOUT,
+            self.redis.pool.xreadgroup(
                 &self.group_name,
                 &self.consumer_name,
                 None,
-                Some(0), // FIXME: set `block` and timeout on the call to prevent the freezes.
+                Some(Self::BLOCK_TIMEOUT.as_millis() as u64),
                 true,
                 vec![&self.keys.position_stream, &self.keys.hardware_stream],
                 vec![XID::NewInGroup, XID::NewInGroup],
-            )
-            .await?;
+            ),
+        )
+        .await
+        {
+            Ok(response) => response?,
+            Err(_) => {
+                // The next iteration retries the read.
+                warn!(timeout = ?Self::READ_TIMEOUT, "timed out while reading the streams");
+                return Ok(None);
+            }
+        };
+        if response.is_null() {
+            trace!("no new entries");
+            return Ok(Some(0));
+        }
+        #[allow(clippy::mutable_key_type)]
+        let response: XReadResponse<RedisKey, String, String, String> =
+            response.into_xread_response()?;
 
+        let mut n_entries = 0;
         for (stream_id, entries) in response {
             info!(stream_id = ?stream_id.inner(), n_entries = entries.len());
+            n_entries += entries.len();
+            self.on_activity().await?;
             if stream_id == self.keys.position_stream {
                 for (entry_id, entry) in entries {
-                    self.on_position_entry(&entry_id, entry.try_into()?).await?;
+                    let span = info_span!("position_entry");
+                    set_parent(&span, &entry);
+                    self.on_position_entry(&entry_id, entry.try_into()?)
+                        .instrument(span)
+                        .await?;
                 }
             } else if stream_id == self.keys.hardware_stream {
                 for (entry_id, entry) in entries {
-                    self.on_hardware_entry(&entry_id, entry.try_into()?).await?;
+                    let span = info_span!("hardware_entry");
+                    set_parent(&span, &entry);
+                    self.on_hardware_entry(&entry_id, entry.try_into()?)
+                        .instrument(span)
+                        .await?;
+                }
+            }
+        }
+
+        Ok(Some(n_entries))
+    }
+
+    /// Remember the entry time and send the recovery notification, if the alert was active.
+    #[instrument(skip_all)]
+    async fn on_activity(&self) -> Result<()> {
+        let opts = self.opts.load();
+        self.redis
+            .pool
+            .set::<(), _, _>(&self.keys.last_entry_at, now(), None, None, false)
+            .await?;
+        if self
+            .redis
+            .pool
+            .del::<i64, _>(&self.keys.stale_alert)
+            .await?
+            != 0
+        {
+            info!("📶 the tracker is back, sending the recovery notification…");
+            let notification = Notification::new("stale-recovered", []);
+            self.send_notification(notification, opts.stale.severity)
+                .await
+                .context("failed to send the recovery notification")?;
+        }
+        Ok(())
+    }
+
+    /// Send the stale tracker alert, if there were no entries for too long.
+    ///
+    /// The alert gets claimed atomically with the staleness check,
+    /// so that it can't race with [`Listener::on_activity`] of another instance.
+    #[instrument(skip_all)]
+    async fn check_staleness(&self) -> Result<()> {
+        let opts = self.opts.load();
+        let now = now();
+        let claimed_at = self
+            .redis
+            .set_nx_if_older(
+                &self.keys.stale_alert,
+                &self.keys.last_entry_at,
+                now - opts.stale.stale_after_secs,
+            )
+            .await?;
+        if let Some(last_entry_at) = claimed_at {
+            let silent_for = now - last_entry_at;
+            warn!(silent_for, "📵 no updates from the tracker, sending the alert…");
+            let notification =
+                Notification::new("stale", [("minutes", (silent_for / 60).to_string())]);
+            self.send_notification(notification, opts.stale.severity)
+                .await
+                .context("failed to send the stale tracker alert")?;
+        }
+        Ok(())
+    }
+
+    /// Send, or delay the notification, depending on its severity and the quiet hours.
+    #[instrument(skip(self, notification), fields(key = notification.key))]
+    async fn send_notification(
+        &self,
+        notification: Notification,
+        severity: Severity,
+    ) -> Result<()> {
+        let opts = self.opts.load();
+        let is_quiet = opts.quiet_hours.is_quiet_at(Utc::now());
+        match severity.delivery(is_quiet) {
+            Delivery::Now => self.broadcast(&notification, false).await,
+            Delivery::Silently => self.broadcast(&notification, true).await,
+            Delivery::Delayed => {
+                info!("🤫 delaying the notification until the quiet hours end…");
+                self.redis
+                    .pool
+                    .rpush::<(), _, _>(
+                        &self.keys.delayed_notifications,
+                        serde_json::to_string(&notification)?,
+                    )
+                    .await?;
+                Ok(())
+            }
+        }
+    }
+
+    /// Send out the delayed notifications, once the quiet hours are over.
+    async fn send_delayed_notifications(&self) -> Result<()> {
+        let opts = self.opts.load();
+        if opts.quiet_hours.is_quiet_at(Utc::now()) {
+            return Ok(());
+        }
+        while let Some(text) = self
+            .redis
+            .pool
+            .lpop::<Option<String>, _>(&self.keys.delayed_notifications, None)
+            .await?
+        {
+            match serde_json::from_str::<Notification>(&text) {
+                Ok(notification) => {
+                    info!(notification.key, "🔔 sending the delayed notification…");
+                    self.broadcast(&notification, false).await?;
+                }
+                Err(error) => {
+                    error!(text, "dropping the invalid delayed notification: {:#}", error)
                 }
             }
         }
+        Ok(())
+    }
 
+    /// Send the notification to all the subscribed chats.
+    ///
+    /// Failing chats are only logged, so that they don't affect the others.
+    async fn broadcast(
+        &self,
+        notification: &Notification,
+        disable_notification: bool,
+    ) -> Result<()> {
+        let chat_locales = self.subscriptions.chat_locales().await?;
+        for chat_id in self.subscriptions.chat_ids().await? {
+            let locale = chat_locales.get(&chat_id).map(String::as_str);
+            if let Err(error) = self
+                .notifier
+                .send(chat_id, locale, notification, disable_notification)
+                .await
+            {
+                error!(chat_id, "failed to send the notification: {:#}", error);
+            }
+        }
         Ok(())
     }
 
     #[instrument(skip_all, fields(entry_id = _entry_id))]
     async fn on_position_entry(&self, _entry_id: &str, entry: PositionEntry) -> Result<()> {
         debug!(entry = ?entry);
-        let location = Location::new(self.chat_id.clone(), entry.latitude, entry.longitude)
-            .horizontal_accuracy(entry.accuracy as f32)
-            .heading(entry.course);
+        if let Err(error) = self.update_zones(&entry).await {
+            error!("failed to update the zones: {:#}", error);
+        }
         info!(
-            latitude = location.latitude,
-            longitude = location.longitude,
-            horizontal_accuracy = location.horizontal_accuracy,
-            heading = location.heading,
+            latitude = entry.latitude,
+            longitude = entry.longitude,
+            accuracy = entry.accuracy,
+            course = entry.course,
             "new location entry",
         );
+        for chat_id in self.subscriptions.chat_ids().await? {
+            let location =
+                Location::new(ChatId::UniqueId(chat_id), entry.latitude, entry.longitude)
+                    .horizontal_accuracy(entry.accuracy as f32)
+                    .heading(entry.course);
+            if let Err(error) = self.update_live_location(chat_id, location).await {
+                error!(chat_id, "failed to update the live location: {:#}", error);
+            }
+        }
+        Ok(())
+    }
 
-        match self
-            .redis
-            .pool
-            .get::<Option<i64>, _>(&self.keys.live_location_message_id)
-            .await?
-        {
-            Some(message_id) => {
-                debug!(message_id, "editing existing message…");
-                if let Err(error) =
-                    EditMessageLiveLocation::new(self.chat_id.clone(), message_id, location)
-                        .call(&self.bot_api)
-                        .await
+    /// Edit the chat's live location, or send a new one.
+    #[instrument(skip(self, location))]
+    async fn update_live_location(&self, chat_id: i64, location: Location) -> Result<()> {
+        let key = self.live_locations.message_id_key(chat_id);
+        if let Some(message_id) = self.redis.pool.get::<Option<i64>, _>(&key).await? {
+            debug!(message_id, "editing existing message…");
+            match EditMessageLiveLocation::new(
+                ChatId::UniqueId(chat_id),
+                message_id,
+                location.clone(),
+            )
+            .call(&self.bot_api)
+            .await
+            {
+                Ok(_) => return Ok(()),
+                Err(error)
+                    if error
+                        .downcast_ref::<ApiError>()
+                        .is_some_and(ApiError::is_message_not_editable) =>
                 {
-                    error!("failed to edit the live location: {:#}", error);
+                    warn!(message_id, "the live location can no longer be edited, rotating…");
+                    self.redis.delete_if_equal(&key, message_id).await?;
                 }
-            }
-            None => {
-                info!("sending a new message…");
-                let message_id = SendLocation::new(location)
-                    .live_period(Self::LIVE_PERIOD)
-                    .call(&self.bot_api)
-                    .await?
-                    .id;
-                debug!(message_id, "updating the live location message ID…");
-                if self
-                    .redis
-                    .pool
-                    .set::<Option<()>, _, _>(
-                        &self.keys.live_location_message_id,
-                        message_id,
-                        Some(Expiration::EX(Self::LIVE_PERIOD.as_secs() as i64)),
-                        Some(SetOptions::NX),
-                        false,
-                    )
-                    .await?
-                    .is_some()
-                {
-                    info!(message_id, "pinning the message…");
-                    PinChatMessage::new(&self.chat_id, message_id)
-                        .disable_notification()
-                        .call(&self.bot_api)
-                        .await?;
-                    self.delete_old_messages().await?;
-                    self.redis
-                        .pool
-                        .rpush(&self.keys.pinned_message_ids, message_id)
-                        .await?;
-                } else {
-                    info!(message_id, "too late – deleting the message…");
-                    DeleteMessage::new(&self.chat_id, message_id)
-                        .call(&self.bot_api)
-                        .await?;
+                Err(error) => {
+                    error!("failed to edit the live location: {:#}", error);
+                    return Ok(());
                 }
             }
-        };
+        }
 
-        Ok(())
+        self.send_live_location(chat_id, location).await
     }
 
-    #[instrument(skip_all)]
-    async fn delete_old_messages(&self) -> Result<()> {
-        while let Some(message_id) = self
+    /// Send and pin a new live location message, replacing the old ones.
+    #[instrument(skip(self, location))]
+    async fn send_live_location(&self, chat_id: i64, location: Location) -> Result<()> {
+        info!("sending a new message…");
+        let message_id = SendLocation::new(location)
+            .live_period(Self::LIVE_PERIOD)
+            .call(&self.bot_api)
+            .await?
+            .id;
+        debug!(message_id, "updating the live location message ID…");
+        if self
             .redis
             .pool
-            .lpop::<Option<i64>, _>(&self.keys.pinned_message_ids, None)
+            .set::<Option<()>, _, _>(
+                self.live_locations.message_id_key(chat_id),
+                message_id,
+                Some(Expiration::EX((Self::LIVE_PERIOD - Self::RENEWAL_MARGIN).as_secs() as i64)),
+                Some(SetOptions::NX),
+                false,
+            )
             .await?
+            .is_some()
         {
-            info!(message_id, "unpinning and deleting the old message…");
-            UnpinChatMessage::new(&self.chat_id, message_id)
+            info!(message_id, "pinning the message…");
+            PinChatMessage::new(chat_id, message_id)
+                .disable_notification()
                 .call(&self.bot_api)
                 .await?;
-            if let Err(error) = DeleteMessage::new(&self.chat_id, message_id)
+            self.live_locations.delete_old_messages(chat_id).await?;
+            self.redis
+                .pool
+                .rpush::<(), _, _>(self.live_locations.pinned_message_ids_key(chat_id), message_id)
+                .await?;
+        } else {
+            info!(message_id, "too late – deleting the message…");
+            DeleteMessage::new(chat_id, message_id)
                 .call(&self.bot_api)
-                .await
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Track the zone transitions and notify about leaving home and entering danger zones.
+    ///
+    /// The new states only get stored, if no other instance has updated them meanwhile.
+    /// Otherwise, the transitions are recomputed from the fresh states.
+    /// The instance which stores the states sends the notifications.
+    #[instrument(skip_all)]
+    async fn update_zones(&self, entry: &PositionEntry) -> Result<()> {
+        let opts = self.opts.load();
+        if opts.geofence.zones.is_empty() {
+            return Ok(());
+        }
+        let point = Point::new(entry.latitude, entry.longitude);
+
+        for attempt in 1..=Self::ZONE_UPDATE_ATTEMPTS {
+            let mut states: HashMap<String, String> =
+                self.redis.pool.hgetall(&self.keys.zone_states).await?;
+            let mut new_states = Vec::new();
+            let mut notifications = Vec::new();
+
+            for zone in &opts.geofence.zones {
+                let old_state = states.remove(&zone.name);
+                let state = match &old_state {
+                    Some(state) => Some(state.parse::<ZoneState>()?),
+                    None => None,
+                };
+                let (new_state, transition) = zone.update(
+                    state,
+                    point,
+                    f64::from(entry.accuracy),
+                    entry.timestamp.timestamp(),
+                    opts.geofence.debounce(),
+                );
+                debug!(zone.name, ?state, ?new_state, ?transition);
+                if state != Some(new_state) {
+                    new_states.push((zone.name.clone(), old_state, new_state.to_string()));
+                }
+
+                let key = match (zone.kind, transition) {
+                    (ZoneKind::Home, Some(Transition::Left)) => "zone-left-home",
+                    (ZoneKind::Danger, Some(Transition::Entered)) => "zone-entered-danger",
+                    (_, Some(transition)) => {
+                        info!(zone.name, ?transition, "zone transition");
+                        continue;
+                    }
+                    (_, None) => continue,
+                };
+                notifications.push((zone.name.clone(), key));
+            }
+
+            // A transition always changes the state, so there's nothing to notify about either.
+            if new_states.is_empty() {
+                return Ok(());
+            }
+            if !self
+                .redis
+                .hset_if_equal(&self.keys.zone_states, new_states)
+                .await?
             {
-                error!("failed to delete the old message: {:#}", error);
+                debug!(attempt, "the zone states have been updated meanwhile, retrying…");
+                continue;
+            }
+
+            for (zone_name, key) in notifications {
+                info!(zone_name, key, "notifying about the zone transition…");
+                let notification = Notification::new(key, [("zone", zone_name)]);
+                self.send_notification(notification, opts.geofence.severity)
+                    .await
+                    .context("failed to send the zone notification")?;
             }
+            return Ok(());
         }
-        Ok(())
+
+        bail!("the zone states keep getting updated concurrently")
     }
 
     #[instrument(skip_all, fields(entry_id = _entry_id))]
@@ -260,29 +620,274 @@ impl Listener {
     ) -> Result<()> {
         info!(current_level, last_level, "battery level changed");
         let last_level = last_level.unwrap_or(current_level);
-        let template_values = HashMap::from([("current_level", current_level.to_string())]);
+        let opts = self.opts.load();
+        let battery = &opts.battery;
 
-        let text = if current_level >= self.battery_opts.full_level
-            && last_level < self.battery_opts.full_level
-        {
-            self.battery_opts.full_message.0.render(&template_values)?
-        } else if current_level <= self.battery_opts.low_level
-            && last_level > self.battery_opts.low_level
-        {
-            self.battery_opts.low_message.0.render(&template_values)?
-        } else if current_level <= self.battery_opts.critical_level {
-            self.battery_opts
-                .critical_message
-                .0
-                .render(&template_values)?
-        } else {
-            return Ok(());
-        };
-        SendMessage::new(&self.chat_id, text)
-            .parse_mode(ParseMode::MarkdownV2)
-            .call(&self.bot_api)
+        let (key, severity) =
+            if current_level >= battery.full_level && last_level < battery.full_level {
+                ("battery-full", battery.full_severity)
+            } else if current_level <= battery.low_level && last_level > battery.low_level {
+                ("battery-low", battery.low_severity)
+            } else if current_level <= battery.critical_level {
+                ("battery-critical", battery.critical_severity)
+            } else {
+                return Ok(());
+            };
+
+        let history = self
+            .tracker
+            .hardware_history(Tracker::HARDWARE_HISTORY_COUNT)
+            .await?;
+        let estimate = DrainEstimate::from_history(&history, battery.drain_window_secs);
+        debug!(?estimate);
+        let notification = Notification::new(
+            key,
+            [
+                ("current_level", current_level.to_string()),
+                (
+                    "drain_per_hour",
+                    estimate.map_or_else(
+                        || "?".to_string(),
+                        |estimate| format!("{:.1}", estimate.drain_per_hour),
+                    ),
+                ),
+                (
+                    "hours_left",
+                    estimate
+                        .and_then(|estimate| estimate.hours_left)
+                        .map_or_else(|| "?".to_string(), |hours_left| format!("{:.0}", hours_left)),
+                ),
+            ],
+        );
+        self.send_notification(notification, severity)
             .await
             .context("failed to send the battery notification")?;
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+    use rusty_shared_heartbeat::status::ServiceStatus;
+    use rusty_shared_telegram::mock::MockServer;
+    use serde_json::json;
+
+    use super::*;
+    use crate::opts::ReadinessOpts;
+
+    /// Redis URL of the listener tests, which require a running Redis server.
+    const REDIS_URL_VAR: &str = "RUSTY_HOME_TEST_REDIS_URL";
+
+    async fn new_listener(server: &MockServer, tracker_id: &str) -> Result<Listener> {
+        let redis_url =
+            std::env::var(REDIS_URL_VAR).unwrap_or_else(|_| "redis://localhost".to_string());
+        let redis = Redis::connect(&redis_url, "test").await?;
+        let bot_api = server.bot_api()?;
+        let context = BotContext {
+            bot_api: bot_api.clone(),
+            redis: redis.clone(),
+            subscriptions: Subscriptions::new(redis.clone(), tracker_id, 42, Vec::new()),
+            live_locations: LiveLocations::new(redis.clone(), bot_api.clone(), tracker_id, 42),
+            tracker: Tracker::new(redis.clone(), tracker_id),
+            opts: Arc::new(Swappable::new(NotificationOpts::try_parse_from(["test"])?)),
+            catalog: Arc::new(Catalog::new("en", None)?),
+        };
+        context.subscriptions.subscribe(100).await?;
+        let readiness = Arc::new(Readiness::new(
+            redis.clone(),
+            bot_api,
+            tracker_id,
+            42,
+            ReadinessOpts::try_parse_from(["test"])?,
+        ));
+        let status = Arc::new(ServiceStatus::new(
+            redis.clone(),
+            "test",
+            "0.0.0",
+            time::Duration::from_secs(60),
+        ));
+        let heartbeat =
+            Heartbeat::new(&rusty_shared_opts::heartbeat::Opts::try_parse_from(["test"])?)?
+                .with_status(status);
+        Listener::new(redis, heartbeat, 42, tracker_id, &context, readiness).await
+    }
+
+    async fn push_position(listener: &Listener, latitude: f64, longitude: f64) -> Result<()> {
+        listener
+            .redis
+            .pool
+            .xadd::<(), _, _, _, _>(
+                &listener.keys.position_stream,
+                false,
+                None,
+                "*",
+                vec![
+                    ("ts", now().to_string()),
+                    ("lat", latitude.to_string()),
+                    ("lon", longitude.to_string()),
+                    ("accuracy", "10".to_string()),
+                ],
+            )
+            .await?;
+        Ok(())
+    }
+
+    fn new_notifier(server: &MockServer, args: &[&str]) -> Result<Notifier> {
+        Ok(Notifier {
+            bot_api: server.bot_api()?,
+            opts: Arc::new(Swappable::new(NotificationOpts::try_parse_from(args)?)),
+            catalog: Arc::new(Catalog::new("en", None)?),
+        })
+    }
+
+    #[async_std::test]
+    async fn notifier_catalog_ok() -> Result<()> {
+        let server = MockServer::start().await?;
+        let notifier = new_notifier(&server, &["test"])?;
+        server.respond("sendMessage", json!({"message_id": 1, "chat": {"id": 100}}));
+
+        let notification = Notification::new("stale", [("minutes", "42".to_string())]);
+        notifier.send(100, Some("nl"), &notification, true).await?;
+
+        let calls = server.calls_of("sendMessage");
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].params["chat_id"], 100);
+        assert_eq!(calls[0].params["parse_mode"], "MarkdownV2");
+        assert_eq!(calls[0].params["disable_notification"], true);
+        assert_eq!(calls[0].params["text"], "📵 Al *42* minuten geen updates van de tracker");
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn notifier_template_ok() -> Result<()> {
+        let server = MockServer::start().await?;
+        let notifier =
+            new_notifier(&server, &["test", "--battery-low-message", "🪫 {current_level}% left"])?;
+        server.respond("sendMessage", json!({"message_id": 1, "chat": {"id": 100}}));
+
+        let notification = Notification::new("battery-low", [("current_level", "15".to_string())]);
+        notifier.send(100, Some("nl"), &notification, false).await?;
+
+        let calls = server.calls_of("sendMessage");
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].params["disable_notification"], false);
+        assert_eq!(calls[0].params["text"], "🪫 15% left");
+        Ok(())
+    }
+
+    #[async_std::test]
+    #[ignore = "requires Redis, see `RUSTY_HOME_TEST_REDIS_URL`"]
+    async fn position_entries_ok() -> Result<()> {
+        let server = MockServer::start().await?;
+        let tracker_id = format!("test-{}-{}", std::process::id(), now());
+        let listener = new_listener(&server, &tracker_id).await?;
+
+        // The first entry recovers from the stale alert and sends a new live location.
+        listener
+            .redis
+            .pool
+            .set::<(), _, _>(&listener.keys.stale_alert, now(), None, None, false)
+            .await?;
+        server
+            .respond("sendMessage", json!({"message_id": 1, "chat": {"id": 100}}))
+            .respond("sendLocation", json!({"message_id": 2, "chat": {"id": 100}}))
+            .respond("pinChatMessage", json!(true));
+        push_position(&listener, 48.85, 2.35).await?;
+        assert_eq!(listener.handle_entries().await?, Some(1));
+
+        let calls = server.calls_of("sendMessage");
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].params["chat_id"], 100);
+        let calls = server.calls_of("sendLocation");
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].params["chat_id"], 100);
+        assert_eq!(calls[0].params["latitude"], 48.85);
+        let calls = server.calls_of("pinChatMessage");
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].params["message_id"], 2);
+
+        // The next one edits the live location.
+        server.respond("editMessageLiveLocation", json!(true));
+        push_position(&listener, 48.86, 2.36).await?;
+        assert_eq!(listener.handle_entries().await?, Some(1));
+
+        let calls = server.calls_of("editMessageLiveLocation");
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].params["chat_id"], 100);
+        assert_eq!(calls[0].params["message_id"], 2);
+        assert_eq!(calls[0].params["latitude"], 48.86);
+        assert_eq!(server.calls_of("sendLocation").len(), 1);
+        Ok(())
+    }
+
+    #[async_std::test]
+    #[ignore = "requires Redis, see `RUSTY_HOME_TEST_REDIS_URL`"]
+    async fn stale_alert_ok() -> Result<()> {
+        let server = MockServer::start().await?;
+        let tracker_id = format!("test-{}-{}", std::process::id(), now());
+        let listener = new_listener(&server, &tracker_id).await?;
+        let stale_after_secs = listener.opts.load().stale.stale_after_secs;
+        let set_last_entry_at = |last_entry_at: i64| {
+            listener.redis.pool.set::<(), _, _>(
+                &listener.keys.last_entry_at,
+                last_entry_at,
+                None,
+                None,
+                false,
+            )
+        };
+
+        // Fresh enough.
+        set_last_entry_at(now() - stale_after_secs + 60).await?;
+        listener.check_staleness().await?;
+        assert!(server.calls_of("sendMessage").is_empty());
+
+        // Stale: the alert goes out only once.
+        server.respond("sendMessage", json!({"message_id": 1, "chat": {"id": 100}}));
+        set_last_entry_at(now() - stale_after_secs - 60).await?;
+        listener.check_staleness().await?;
+        listener.check_staleness().await?;
+        assert_eq!(server.calls_of("sendMessage").len(), 1);
+
+        // The activity recovers.
+        server.respond("sendMessage", json!({"message_id": 2, "chat": {"id": 100}}));
+        listener.on_activity().await?;
+        assert_eq!(server.calls_of("sendMessage").len(), 2);
+        Ok(())
+    }
+
+    /// The staleness check of one instance must not leave the alert active,
+    /// when it runs concurrently with the activity on another instance.
+    #[async_std::test]
+    #[ignore = "requires Redis, see `RUSTY_HOME_TEST_REDIS_URL`"]
+    async fn stale_alert_activity_race_ok() -> Result<()> {
+        let server = MockServer::start().await?;
+        let tracker_id = format!("test-{}-{}", std::process::id(), now());
+        let listener = new_listener(&server, &tracker_id).await?;
+        let other_listener = new_listener(&server, &tracker_id).await?;
+        let stale_after_secs = listener.opts.load().stale.stale_after_secs;
+
+        for _ in 0..50 {
+            listener
+                .redis
+                .pool
+                .set::<(), _, _>(
+                    &listener.keys.last_entry_at,
+                    now() - stale_after_secs - 60,
+                    None,
+                    None,
+                    false,
+                )
+                .await?;
+            futures::try_join!(listener.on_activity(), other_listener.check_staleness())?;
+            let is_alert_active = listener
+                .redis
+                .pool
+                .exists::<bool, _>(&listener.keys.stale_alert)
+                .await?;
+            assert!(!is_alert_active, "the alert is active after the activity");
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,113 @@
+//! Pinned live location messages of the subscribed chats.
+
+use fred::prelude::*;
+use fred::types::RedisKey;
+use rusty_shared_redis::Redis;
+use rusty_shared_telegram::api::BotApi;
+use rusty_shared_telegram::methods::*;
+
+use crate::prelude::*;
+
+#[derive(Clone)]
+pub struct LiveLocations {
+    redis: Redis,
+    bot_api: BotApi,
+
+    /// Common prefix of the keys, which are specific to the tracker and the bot.
+    prefix: String,
+}
+
+impl LiveLocations {
+    pub fn new(redis: Redis, bot_api: BotApi, tracker_id: &str, bot_user_id: i64) -> Self {
+        Self {
+            redis,
+            bot_api,
+            prefix: format!("rusty:tractive:{}:telegram:{}", tracker_id, bot_user_id),
+        }
+    }
+
+    /// Stores the chat's pinned message IDs, so that we can unpin them later.
+    pub fn pinned_message_ids_key(&self, chat_id: i64) -> RedisKey {
+        RedisKey::from(format!("{}:{}:pinned_message_ids", self.prefix, chat_id))
+    }
+
+    /// The chat's live location message ID, so that we can update it at any time.
+    pub fn message_id_key(&self, chat_id: i64) -> RedisKey {
+        RedisKey::from(format!("{}:{}:live_location_message_id", self.prefix, chat_id))
+    }
+
+    /// Stop, unpin and delete the chat's live location, once the chat is unsubscribed.
+    #[instrument(skip(self))]
+    pub async fn remove(&self, chat_id: i64) -> Result<()> {
+        info!("removing the live location…");
+        self.redis
+            .pool
+            .del::<(), _>(self.message_id_key(chat_id))
+            .await?;
+        self.delete_old_messages(chat_id).await
+    }
+
+    /// Stop, unpin and delete the chat's pinned messages.
+    #[instrument(skip(self))]
+    pub async fn delete_old_messages(&self, chat_id: i64) -> Result<()> {
+        while let Some(message_id) = self
+            .redis
+            .pool
+            .lpop::<Option<i64>, _>(self.pinned_message_ids_key(chat_id), None)
+            .await?
+        {
+            info!(message_id, "stopping, unpinning and deleting the old message…");
+            if let Err(error) = StopMessageLiveLocation::new(chat_id, message_id)
+                .call(&self.bot_api)
+                .await
+            {
+                // The live period may be already over.
+                debug!("failed to stop the old live location: {:#}", error);
+            }
+            UnpinChatMessage::new(chat_id, message_id)
+                .call(&self.bot_api)
+                .await?;
+            if let Err(error) = DeleteMessage::new(chat_id, message_id)
+                .call(&self.bot_api)
+                .await
+            {
+                error!("failed to delete the old message: {:#}", error);
+            }
+        }
+        Ok(())
+    }
+
+    /// Hand the keys of the single-chat versions over to the chat, which used to be `--chat-id`.
+    ///
+    /// The old pinned messages then get deleted along with the chat's next live location,
+    /// and without the chat, the keys are just dropped.
+    #[instrument(skip(self))]
+    pub async fn migrate_legacy_keys(&self, chat_id: Option<i64>) -> Result<()> {
+        let pinned_message_ids_key = RedisKey::from(format!("{}:pinned_message_ids", self.prefix));
+        let message_id_key = RedisKey::from(format!("{}:live_location_message_id", self.prefix));
+
+        if let Some(chat_id) = chat_id {
+            while let Some(message_id) = self
+                .redis
+                .pool
+                .lpop::<Option<i64>, _>(&pinned_message_ids_key, None)
+                .await?
+            {
+                info!(message_id, "migrating the pinned message…");
+                self.redis
+                    .pool
+                    .rpush::<(), _, _>(self.pinned_message_ids_key(chat_id), message_id)
+                    .await?;
+            }
+        }
+        let n_deleted = self
+            .redis
+            .pool
+            .del::<i64, _>(vec![pinned_message_ids_key, message_id_key])
+            .await?;
+        if n_deleted != 0 {
+            warn!(n_deleted, "deleted the legacy keys");
+        }
+        Ok(())
+    }
+}
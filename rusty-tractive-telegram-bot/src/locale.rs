@@ -0,0 +1,170 @@
+//! Localized message catalogs.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::Path;
+
+use anyhow::{anyhow, bail};
+use new_string_template::template::Template;
+
+use crate::opts::TemplateArg;
+use crate::prelude::*;
+
+/// Messages of a single locale by their keys.
+type Messages = HashMap<String, String>;
+
+pub struct Catalog {
+    default_locale: String,
+
+    /// Messages by locale.
+    locales: BTreeMap<String, Messages>,
+}
+
+impl Catalog {
+    const BUILT_IN: [(&'static str, &'static str); 2] = [
+        ("en", include_str!("../locales/en.toml")),
+        ("nl", include_str!("../locales/nl.toml")),
+    ];
+    /// Locale of the last resort, it must have all the messages.
+    const FALLBACK_LOCALE: &'static str = "en";
+
+    /// Load the built-in catalogs, and then the `<locale>.toml` catalogs from the directory,
+    /// which override and extend the built-in ones.
+    pub fn new(default_locale: &str, dir: Option<&Path>) -> Result<Self> {
+        let mut locales = BTreeMap::new();
+        for (locale, contents) in Self::BUILT_IN {
+            locales.insert(locale.to_string(), toml::from_str::<Messages>(contents)?);
+        }
+        let mut this = Self {
+            default_locale: default_locale.to_string(),
+            locales,
+        };
+        if let Some(dir) = dir {
+            this.load_dir(dir)
+                .with_context(|| format!("failed to load the catalogs from `{}`", dir.display()))?;
+        }
+        if !this.has_locale(default_locale) {
+            bail!("the default locale `{}` is not available", default_locale);
+        }
+        Ok(this)
+    }
+
+    fn load_dir(&mut self, dir: &Path) -> Result<()> {
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.extension().is_none_or(|extension| extension != "toml") {
+                continue;
+            }
+            let locale = match path.file_stem().and_then(|stem| stem.to_str()) {
+                Some(locale) => locale.to_string(),
+                None => continue,
+            };
+            let messages: Messages = toml::from_str(&fs::read_to_string(&path)?)
+                .with_context(|| format!("invalid catalog `{}`", path.display()))?;
+            if let Some(key) = messages
+                .keys()
+                .find(|key| !self.locales[Self::FALLBACK_LOCALE].contains_key(*key))
+            {
+                bail!("unknown message `{}` in `{}`", key, path.display());
+            }
+            info!(locale, n_messages = messages.len(), "loaded the catalog");
+            self.locales.entry(locale).or_default().extend(messages);
+        }
+        Ok(())
+    }
+
+    pub fn has_locale(&self, locale: &str) -> bool {
+        self.locales.contains_key(locale)
+    }
+
+    /// The available locales, comma-separated.
+    pub fn locale_list(&self) -> String {
+        self.locales
+            .keys()
+            .map(String::as_str)
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// The chat locale, if it's available, or the default one.
+    pub fn resolve<'a>(&'a self, locale: Option<&'a str>) -> &'a str {
+        locale
+            .filter(|locale| self.has_locale(locale))
+            .unwrap_or(&self.default_locale)
+    }
+
+    /// Look up the message in the locale, falling back to the default and then English.
+    fn get(&self, locale: Option<&str>, key: &str) -> Result<&str> {
+        [
+            self.resolve(locale),
+            &self.default_locale,
+            Self::FALLBACK_LOCALE,
+        ]
+        .into_iter()
+        .find_map(|locale| self.locales.get(locale)?.get(key))
+        .map(String::as_str)
+        .ok_or_else(|| anyhow!("missing message `{}`", key))
+    }
+
+    /// Render the MarkdownV2 message, escaping the values.
+    pub fn render(
+        &self,
+        locale: Option<&str>,
+        key: &str,
+        values: &HashMap<&str, String>,
+    ) -> Result<String> {
+        TemplateArg(Template::new(self.get(locale, key)?)).render(values)
+    }
+
+    /// Render the plain text `*-text` message, which is meant to be a value of another message.
+    pub fn text(
+        &self,
+        locale: Option<&str>,
+        key: &str,
+        values: &HashMap<&str, String>,
+    ) -> Result<String> {
+        Ok(Template::new(self.get(locale, key)?).render(values)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn built_in_catalogs_complete_ok() -> Result<()> {
+        let catalog = Catalog::new("en", None)?;
+        let english = &catalog.locales[Catalog::FALLBACK_LOCALE];
+        for (locale, messages) in &catalog.locales {
+            for key in english.keys() {
+                assert!(messages.contains_key(key), "`{}` is missing in `{}`", key, locale);
+            }
+            assert_eq!(messages.len(), english.len(), "`{}` has unknown messages", locale);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn render_ok() -> Result<()> {
+        let catalog = Catalog::new("nl", None)?;
+        let values = HashMap::from([("zone", "Home (1.5)".to_string())]);
+        assert_eq!(
+            catalog.render(Some("en"), "zone-left-home", &values)?,
+            r#"🏃 Left *Home \(1\.5\)*"#,
+        );
+        assert_eq!(
+            catalog.render(Some("unknown"), "zone-left-home", &values)?,
+            r#"🏃 *Home \(1\.5\)* verlaten"#,
+        );
+        assert_eq!(
+            catalog.render(None, "no-position", &HashMap::new())?,
+            r#"🤷 Nog geen positie\."#
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn unknown_default_locale_error() {
+        assert!(Catalog::new("fr", None).is_err());
+    }
+}
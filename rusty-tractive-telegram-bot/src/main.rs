@@ -8,51 +8,146 @@
     clippy::needless_pass_by_value
 )]
 
+use std::sync::Arc;
 use std::time::Duration;
 
-use anyhow::Result;
-use clap::Parser;
-use futures::future::try_join;
+use futures::future::try_join4;
+use rusty_shared_heartbeat::status::ServiceStatus;
+use rusty_shared_heartbeat::Heartbeat;
+use rusty_shared_opts::systemd;
 use rusty_shared_telegram::api::BotApi;
 use rusty_shared_telegram::methods;
 use rusty_shared_telegram::methods::Method;
 
 use crate::listener::Listener;
+use crate::live_location::LiveLocations;
+use crate::locale::Catalog;
 use crate::opts::Opts;
+use crate::prelude::*;
+use crate::readiness::Readiness;
+use crate::reload::{ConfigReloader, Swappable};
+use crate::subscriptions::Subscriptions;
+use crate::tracker::Tracker;
+use crate::webhook::WebhookMonitor;
 
+mod battery;
 mod bot;
+mod geofence;
 mod listener;
+mod live_location;
+mod locale;
 mod middleware;
 mod opts;
 mod prelude;
+mod quiet_hours;
+mod readiness;
+mod reload;
+mod subscriptions;
+mod tracker;
+mod webhook;
 
 static BIN_NAME: &str = env!("CARGO_BIN_NAME");
 
 #[async_std::main]
 async fn main() -> Result<()> {
-    let opts: Opts = Opts::parse();
-    let _guard = rusty_shared_tracing::init(opts.sentry, BIN_NAME)?;
+    let opts: Opts = rusty_shared_opts::config::parse()?;
+    let _guard = rusty_shared_tracing::init(opts.sentry, opts.log, opts.otlp, BIN_NAME)?;
+    let shutdown = opts.shutdown.install()?;
+    // Before anything else, so that the startup failures get reported too.
+    let heartbeat = Heartbeat::new(&opts.heartbeat)?;
 
-    let bot_api = BotApi::new(opts.service.bot_token, Duration::from_secs(5))?;
-    let me = methods::GetMe.call(&bot_api).await?;
-    let redis = rusty_shared_redis::Redis::connect(&opts.redis.redis_url, BIN_NAME).await?;
+    let service_future = async {
+        opts.service.notifications.validate()?;
+        let bot_api = BotApi::new(
+            &opts.service.bot_api_url,
+            &opts.service.bot_token,
+            Duration::from_secs(5),
+        )?;
+        systemd::notify_status("connecting to Telegram and Redis");
+        let me = methods::GetMe.call(&bot_api).await?;
+        let redis = rusty_shared_redis::Redis::connect(&opts.redis.redis_url, BIN_NAME).await?;
+        // The listener blocks on `XREADGROUP`, which would fail the concurrent commands.
+        let listener_redis =
+            rusty_shared_redis::Redis::connect(&opts.redis.redis_url, BIN_NAME).await?;
 
-    let listener = {
-        let bot_api = bot_api.clone();
         let tracker_id = opts.service.tracker_id.to_lowercase();
-        let chat_id = opts.service.chat_id;
-        let heartbeat = opts.heartbeat.get_heartbeat()?;
-        let battery_opts = opts.service.battery;
-        Listener::new(redis, bot_api, heartbeat, me.id, &tracker_id, chat_id, battery_opts).await?
+        let subscriptions =
+            Subscriptions::new(redis.clone(), &tracker_id, me.id, opts.service.allowed_user_ids);
+        if let Some(chat_id) = opts.service.chat_id {
+            subscriptions.subscribe(chat_id).await?;
+        }
+        let live_locations = LiveLocations::new(redis.clone(), bot_api.clone(), &tracker_id, me.id);
+        live_locations
+            .migrate_legacy_keys(opts.service.chat_id)
+            .await?;
+        let catalog =
+            Arc::new(Catalog::new(&opts.service.locale, opts.service.locales_dir.as_deref())?);
+        let notification_opts = Arc::new(Swappable::new(opts.service.notifications));
+        let readiness = Arc::new(Readiness::new(
+            redis.clone(),
+            bot_api.clone(),
+            &tracker_id,
+            me.id,
+            opts.service.readiness,
+        ));
+        let webhook_monitor = Arc::new(WebhookMonitor::new(
+            bot_api.clone(),
+            opts.service.webhook_url,
+            Duration::from_secs(opts.service.webhook_check_interval_secs),
+            opts.service.webhook_pending_update_threshold,
+            opts.service.drop_pending_updates,
+        ));
+        let bot_context = bot::BotContext {
+            bot_api,
+            redis: redis.clone(),
+            subscriptions,
+            live_locations,
+            tracker: Tracker::new(redis.clone(), &tracker_id),
+            opts: notification_opts.clone(),
+            catalog,
+        };
+        let status = Arc::new(ServiceStatus::new(
+            redis.clone(),
+            BIN_NAME,
+            env!("CARGO_PKG_VERSION"),
+            Duration::from_secs(opts.heartbeat.status_ttl_secs),
+        ));
+        let reloader = ConfigReloader::new(
+            redis,
+            opts.config.config_path,
+            &tracker_id,
+            me.id,
+            Duration::from_secs(opts.service.config_reload_interval_secs),
+            notification_opts,
+        );
+
+        let listener = Listener::new(
+            listener_redis,
+            heartbeat.clone().with_status(status.clone()),
+            me.id,
+            &tracker_id,
+            &bot_context,
+            readiness.clone(),
+        )
+        .await?;
+        let listener_future = listener.run(&shutdown);
+
+        let bot_future = bot::run(
+            bot_context,
+            readiness,
+            webhook_monitor,
+            me.username,
+            opts.service.bind_endpoint,
+            opts.service.secret_token,
+            &shutdown,
+        );
+        let reloader_future = reloader.run(&shutdown);
+        let status_future = status.run(&shutdown);
+        try_join4(bot_future, listener_future, reloader_future, status_future).await
     };
-    let listener_future = listener.run();
-
-    let bot_future = bot::run(
-        bot_api,
-        opts.service.bind_endpoint,
-        opts.service.webhook_url,
-        opts.service.secret_token,
-    );
-    try_join(bot_future, listener_future).await?;
-    Ok(())
+    let result = shutdown.run(service_future).await;
+    if let Err(error) = &result {
+        heartbeat.fail(error).await;
+    }
+    result.map(|_| ())
 }
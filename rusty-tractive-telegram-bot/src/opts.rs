@@ -1,23 +1,44 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
 use std::str::FromStr;
 
-use anyhow::Error;
+use anyhow::{Error, Result};
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
 use clap::Parser;
 use new_string_template::template::Template;
-use rusty_shared_opts::{heartbeat, redis, sentry};
+use rusty_shared_opts::{config, heartbeat, log, otlp, redis, sentry, shutdown};
+use rusty_shared_telegram::api::BotApi;
+use rusty_shared_telegram::models::ParseMode;
 use secstr::SecUtf8;
 
+use crate::geofence::{ensure_unique_names, Debounce, Zone};
+use crate::quiet_hours::{QuietHours, Severity};
+
 #[derive(Parser)]
 #[clap(author, version, about)]
 pub struct Opts {
+    #[clap(flatten)]
+    pub config: config::Opts,
+
     #[clap(flatten)]
     pub redis: redis::Opts,
 
     #[clap(flatten)]
     pub sentry: sentry::Opts,
 
+    #[clap(flatten)]
+    pub log: log::Opts,
+
+    #[clap(flatten)]
+    pub otlp: otlp::Opts,
+
     #[clap(flatten)]
     pub heartbeat: heartbeat::Opts,
 
+    #[clap(flatten)]
+    pub shutdown: shutdown::Opts,
+
     #[clap(flatten)]
     pub service: ServiceOpts,
 }
@@ -28,6 +49,15 @@ pub struct ServiceOpts {
     #[clap(long, env = "RUSTY_TELEGRAM_BOT_TOKEN")]
     pub bot_token: String,
 
+    /// Telegram Bot API server URL.
+    /// Allows using a [self-hosted](https://github.com/tdlib/telegram-bot-api) Bot API server.
+    #[clap(
+        long,
+        env = "RUSTY_TELEGRAM_BOT_API_URL",
+        default_value = BotApi::DEFAULT_BASE_URL
+    )]
+    pub bot_api_url: String,
+
     /// Telegram Bot API [webhook](https://core.telegram.org/bots/webhooks) endpoint.
     #[clap(
         long,
@@ -44,16 +74,130 @@ pub struct ServiceOpts {
     #[clap(long, env = "RUSTY_TELEGRAM_BOT_SECRET_TOKEN")]
     pub secret_token: SecUtf8,
 
+    /// Interval between the [`getWebhookInfo`](https://core.telegram.org/bots/api#getwebhookinfo) checks, in seconds.
+    #[clap(
+        long,
+        env = "RUSTY_TELEGRAM_BOT_WEBHOOK_CHECK_INTERVAL",
+        default_value = "300"
+    )]
+    pub webhook_check_interval_secs: u64,
+
+    /// Number of the pending updates, at which the webhook check logs a warning.
+    #[clap(
+        long,
+        env = "RUSTY_TELEGRAM_BOT_WEBHOOK_PENDING_UPDATE_THRESHOLD",
+        default_value = "10"
+    )]
+    pub webhook_pending_update_threshold: u32,
+
+    /// Drop the updates, which have piled up while the bot was down,
+    /// when the webhook gets registered on start.
+    ///
+    /// The updates are dropped for all the instances, so pass it once to a single instance,
+    /// instead of keeping it in the environment.
+    #[clap(long)]
+    pub drop_pending_updates: bool,
+
     /// Tractive tracker ID (case-insensitive).
     #[clap(long, env = "RUSTY_TRACTIVE_TRACKER_ID")]
     pub tracker_id: String,
 
-    /// Target chat to which the updates will be posted.
+    /// Chat which gets subscribed to the updates on start, in addition to the `/subscribe`d ones.
     #[clap(long, env = "RUSTY_TRACTIVE_CHAT_ID")]
-    pub chat_id: i64,
+    pub chat_id: Option<i64>,
+
+    /// User allowed to `/subscribe` and `/unsubscribe` chats.
+    #[clap(
+        long = "allowed-user-id",
+        alias = "allowed-user-ids",
+        env = "RUSTY_TRACTIVE_ALLOWED_USER_IDS",
+        multiple_occurrences = true,
+        value_delimiter = ','
+    )]
+    pub allowed_user_ids: Vec<i64>,
+
+    /// Interval between the configuration reloads, in seconds.
+    /// The notification settings are reloaded from the configuration file
+    /// and the `rusty:tractive:<tracker>:telegram:<bot>:config` Redis hash, which overrides the file.
+    #[clap(
+        long = "config-reload-interval",
+        env = "RUSTY_TRACTIVE_CONFIG_RELOAD_INTERVAL",
+        default_value = "60"
+    )]
+    pub config_reload_interval_secs: u64,
+
+    /// Default locale of the messages: `en` or `nl`, unless more are added with `--locales-dir`.
+    #[clap(long, env = "RUSTY_TRACTIVE_LOCALE", default_value = "en")]
+    pub locale: String,
+
+    /// Directory of the `<locale>.toml` message catalogs,
+    /// which override and extend the built-in ones.
+    #[clap(long, env = "RUSTY_TRACTIVE_LOCALES_DIR")]
+    pub locales_dir: Option<PathBuf>,
+
+    #[clap(flatten)]
+    pub notifications: NotificationOpts,
 
+    #[clap(flatten)]
+    pub readiness: ReadinessOpts,
+}
+
+/// Thresholds of the `/health/ready` endpoint.
+#[derive(Parser)]
+pub struct ReadinessOpts {
+    /// Maximum time, in seconds, since the listener's last successful read.
+    #[clap(
+        long = "ready-listener-max-age",
+        env = "RUSTY_TRACTIVE_READY_LISTENER_MAX_AGE",
+        default_value = "60"
+    )]
+    pub listener_max_age_secs: i64,
+
+    /// Maximum time, in seconds, since the last successful Telegram Bot API call.
+    /// The webhook checks make sure there's at least one per `--webhook-check-interval-secs`.
+    #[clap(
+        long = "ready-telegram-max-age",
+        env = "RUSTY_TRACTIVE_READY_TELEGRAM_MAX_AGE",
+        default_value = "900"
+    )]
+    pub telegram_max_age_secs: i64,
+}
+
+/// Options of the notifications which the listener sends out.
+#[derive(Parser)]
+pub struct NotificationOpts {
     #[clap(flatten)]
     pub battery: BatteryOpts,
+
+    #[clap(flatten)]
+    pub geofence: GeofenceOpts,
+
+    #[clap(flatten)]
+    pub stale: StaleOpts,
+
+    #[clap(flatten)]
+    pub quiet_hours: QuietHoursOpts,
+}
+
+impl NotificationOpts {
+    /// Check the constraints which can't be expressed with the individual arguments.
+    pub fn validate(&self) -> Result<()> {
+        ensure_unique_names(&self.geofence.zones)
+    }
+
+    /// The template which overrides the catalog message, if any.
+    pub fn template(&self, key: &str) -> Option<&TemplateArg> {
+        match key {
+            "battery-full" => self.battery.full_message.as_ref(),
+            "battery-low" => self.battery.low_message.as_ref(),
+            "battery-critical" => self.battery.critical_message.as_ref(),
+            "zone-left-home" => self.geofence.left_home_message.as_ref(),
+            "zone-entered-danger" => self.geofence.entered_danger_message.as_ref(),
+            "stale" => self.stale.stale_message.as_ref(),
+            "stale-recovered" => self.stale.recovered_message.as_ref(),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Parser)]
@@ -66,15 +210,25 @@ pub struct BatteryOpts {
     )]
     pub full_level: u8,
 
-    /// Full battery message template.
-    /// The template is rendered as MarkdownV2.
+    /// Full battery message template, which overrides the catalog message in all the locales.
+    /// The message templates are rendered as MarkdownV2, the placeholder values get escaped automatically.
+    /// The battery templates may use `{current_level}`, `{drain_per_hour}` and `{hours_left}`,
+    /// the latter two are `?` until there's enough history.
     #[clap(
         long = "battery-full-message",
         env = "RUSTY_TRACTIVE_BATTERY_FULL_MESSAGE",
-        default_value = r#"🔋 *{current_level}%* Battery is now full\!"#,
         next_line_help = true
     )]
-    pub full_message: TemplateArg,
+    pub full_message: Option<TemplateArg>,
+
+    /// Full battery notification severity.
+    #[clap(
+        long = "battery-full-severity",
+        env = "RUSTY_TRACTIVE_BATTERY_FULL_SEVERITY",
+        value_enum,
+        default_value = "low"
+    )]
+    pub full_severity: Severity,
 
     /// Maximum battery level which is treated as low.
     #[clap(
@@ -88,10 +242,18 @@ pub struct BatteryOpts {
     #[clap(
         long = "battery-low-message",
         env = "RUSTY_TRACTIVE_BATTERY_LOW_MESSAGE",
-        default_value = "⚡️ *{current_level}%* battery level is getting low️",
         next_line_help = true
     )]
-    pub low_message: TemplateArg,
+    pub low_message: Option<TemplateArg>,
+
+    /// Low battery notification severity.
+    #[clap(
+        long = "battery-low-severity",
+        env = "RUSTY_TRACTIVE_BATTERY_LOW_SEVERITY",
+        value_enum,
+        default_value = "normal"
+    )]
+    pub low_severity: Severity,
 
     /// Maximum battery level which is treated as critically low.
     #[clap(
@@ -105,18 +267,192 @@ pub struct BatteryOpts {
     #[clap(
         long = "battery-critical-message",
         env = "RUSTY_TRACTIVE_BATTERY_CRITICAL_MESSAGE",
-        default_value = "🪫 *{current_level}%* battery level is critical️",
         next_line_help = true
     )]
-    pub critical_message: TemplateArg,
+    pub critical_message: Option<TemplateArg>,
+
+    /// Critically low battery notification severity.
+    #[clap(
+        long = "battery-critical-severity",
+        env = "RUSTY_TRACTIVE_BATTERY_CRITICAL_SEVERITY",
+        value_enum,
+        default_value = "critical"
+    )]
+    pub critical_severity: Severity,
+
+    /// Time window, in seconds, over which the battery drain rate is estimated.
+    #[clap(
+        long = "battery-drain-window",
+        env = "RUSTY_TRACTIVE_BATTERY_DRAIN_WINDOW",
+        default_value = "86400"
+    )]
+    pub drain_window_secs: i64,
+}
+
+#[derive(Parser)]
+pub struct GeofenceOpts {
+    /// Named zone: `<kind>:<name>=<geometry>`.
+    /// `kind` is `home` (notify on leaving) or `danger` (notify on entering).
+    /// `geometry` is a circle `<latitude>,<longitude>,<radius in meters>`,
+    /// or a polygon of `<latitude>,<longitude>` vertices separated with `;`.
+    #[clap(
+        long = "zone",
+        alias = "zones",
+        env = "RUSTY_TRACTIVE_ZONES",
+        multiple_occurrences = true,
+        value_delimiter = '|',
+        next_line_help = true
+    )]
+    pub zones: Vec<Zone>,
+
+    /// Distance beyond a zone boundary, in meters, required to enter or leave the zone.
+    /// The position accuracy is used instead when it's worse.
+    #[clap(
+        long = "zone-hysteresis",
+        env = "RUSTY_TRACTIVE_ZONE_HYSTERESIS",
+        default_value = "25"
+    )]
+    pub hysteresis: f64,
+
+    /// Minimal time, in seconds, during which the positions must stay beyond the boundary.
+    #[clap(
+        long = "zone-min-dwell-time",
+        env = "RUSTY_TRACTIVE_ZONE_MIN_DWELL_TIME",
+        default_value = "120"
+    )]
+    pub min_dwell_time_secs: i64,
+
+    /// Message template for leaving a home zone.
+    #[clap(
+        long = "zone-left-home-message",
+        env = "RUSTY_TRACTIVE_ZONE_LEFT_HOME_MESSAGE",
+        next_line_help = true
+    )]
+    pub left_home_message: Option<TemplateArg>,
+
+    /// Message template for entering a danger zone.
+    #[clap(
+        long = "zone-entered-danger-message",
+        env = "RUSTY_TRACTIVE_ZONE_ENTERED_DANGER_MESSAGE",
+        next_line_help = true
+    )]
+    pub entered_danger_message: Option<TemplateArg>,
+
+    /// Severity of the zone notifications.
+    #[clap(
+        id = "zone-severity",
+        long = "zone-severity",
+        env = "RUSTY_TRACTIVE_ZONE_SEVERITY",
+        value_enum,
+        default_value = "critical"
+    )]
+    pub severity: Severity,
+}
+
+impl GeofenceOpts {
+    pub const fn debounce(&self) -> Debounce {
+        Debounce {
+            hysteresis: self.hysteresis,
+            min_dwell_time: self.min_dwell_time_secs,
+        }
+    }
+}
+
+#[derive(Parser)]
+pub struct StaleOpts {
+    /// Time, in seconds, without any new position or hardware entries,
+    /// after which the tracker is considered stale.
+    #[clap(
+        long = "stale-after",
+        env = "RUSTY_TRACTIVE_STALE_AFTER",
+        default_value = "7200"
+    )]
+    pub stale_after_secs: i64,
+
+    /// Stale tracker alert template.
+    #[clap(
+        long = "stale-message",
+        env = "RUSTY_TRACTIVE_STALE_MESSAGE",
+        next_line_help = true
+    )]
+    pub stale_message: Option<TemplateArg>,
+
+    /// Message template for when the updates resume after the alert.
+    #[clap(
+        long = "stale-recovered-message",
+        env = "RUSTY_TRACTIVE_STALE_RECOVERED_MESSAGE",
+        next_line_help = true
+    )]
+    pub recovered_message: Option<TemplateArg>,
+
+    /// Severity of the stale tracker alert and the recovery notification.
+    #[clap(
+        id = "stale-severity",
+        long = "stale-severity",
+        env = "RUSTY_TRACTIVE_STALE_SEVERITY",
+        value_enum,
+        default_value = "normal"
+    )]
+    pub severity: Severity,
+}
+
+#[derive(Parser)]
+pub struct QuietHoursOpts {
+    /// Daily quiet hours, for example: `22:00-07:00`.
+    /// During the quiet hours, `low` severity notifications are delayed until the end,
+    /// `normal` ones are sent silently, and `critical` ones are sent as usual.
+    #[clap(long = "quiet-hours", env = "RUSTY_TRACTIVE_QUIET_HOURS")]
+    pub quiet_hours: Option<QuietHours>,
+
+    /// Time zone of the quiet hours, for example: `Europe/Amsterdam`.
+    #[clap(
+        long = "time-zone",
+        env = "RUSTY_TRACTIVE_TIME_ZONE",
+        default_value = "UTC"
+    )]
+    pub time_zone: Tz,
+}
+
+impl QuietHoursOpts {
+    pub fn is_quiet_at(&self, datetime: DateTime<Utc>) -> bool {
+        self.quiet_hours.is_some_and(|quiet_hours| {
+            quiet_hours.contains(datetime.with_timezone(&self.time_zone).time())
+        })
+    }
 }
 
 pub struct TemplateArg(pub Template);
 
+impl TemplateArg {
+    /// Parse mode in which the templates are written.
+    pub const PARSE_MODE: ParseMode = ParseMode::MarkdownV2;
+
+    /// Render the template, escaping the values.
+    pub fn render(&self, values: &HashMap<&str, String>) -> Result<String> {
+        let values: HashMap<&str, _> = values
+            .iter()
+            .map(|(key, value)| (*key, Self::PARSE_MODE.escape(value)))
+            .collect();
+        Ok(self.0.render(&values)?)
+    }
+}
+
 impl FromStr for TemplateArg {
     type Err = Error;
 
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
+    fn from_str(s: &str) -> Result<Self> {
         Ok(TemplateArg(Template::new(s)))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use clap::CommandFactory;
+
+    use super::*;
+
+    #[test]
+    fn command_ok() {
+        Opts::command().debug_assert();
+    }
+}
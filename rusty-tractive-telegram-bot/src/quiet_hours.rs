@@ -0,0 +1,128 @@
+//! Quiet hours and notification severities.
+
+use std::str::FromStr;
+
+use anyhow::{anyhow, Error};
+use chrono::NaiveTime;
+use clap::ValueEnum;
+
+use crate::prelude::*;
+
+/// Daily local time range, which may wrap around midnight.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct QuietHours {
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+}
+
+impl QuietHours {
+    /// Check whether the time is within the range. The end is exclusive.
+    pub fn contains(&self, time: NaiveTime) -> bool {
+        if self.start <= self.end {
+            self.start <= time && time < self.end
+        } else {
+            self.start <= time || time < self.end
+        }
+    }
+}
+
+/// Parses `<start>-<end>`, for example: `22:00-07:00`.
+impl FromStr for QuietHours {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (start, end) = s
+            .split_once('-')
+            .ok_or_else(|| anyhow!("expected `<start>-<end>`, got `{}`", s))?;
+        let parse = |time: &str| {
+            NaiveTime::parse_from_str(time.trim(), "%H:%M")
+                .with_context(|| format!("invalid time `{}`", time))
+        };
+        Ok(Self {
+            start: parse(start)?,
+            end: parse(end)?,
+        })
+    }
+}
+
+/// Decides what may break through the quiet hours.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ValueEnum)]
+pub enum Severity {
+    /// Delayed until the quiet hours end.
+    Low,
+
+    /// Sent silently during the quiet hours.
+    Normal,
+
+    /// Always sent with a sound.
+    Critical,
+}
+
+/// How a notification gets delivered.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Delivery {
+    Now,
+    Silently,
+    Delayed,
+}
+
+impl Severity {
+    pub const fn delivery(self, is_quiet: bool) -> Delivery {
+        match (self, is_quiet) {
+            (_, false) | (Self::Critical, true) => Delivery::Now,
+            (Self::Normal, true) => Delivery::Silently,
+            (Self::Low, true) => Delivery::Delayed,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn time(hour: u32, minute: u32) -> NaiveTime {
+        NaiveTime::from_hms(hour, minute, 0)
+    }
+
+    #[test]
+    fn parse_ok() -> Result<()> {
+        assert_eq!(
+            "22:00-07:30".parse::<QuietHours>()?,
+            QuietHours {
+                start: time(22, 0),
+                end: time(7, 30),
+            },
+        );
+        assert!("22:00".parse::<QuietHours>().is_err());
+        assert!("25:00-07:00".parse::<QuietHours>().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn contains_ok() -> Result<()> {
+        let quiet_hours: QuietHours = "13:00-15:00".parse()?;
+        assert!(quiet_hours.contains(time(13, 0)));
+        assert!(quiet_hours.contains(time(14, 59)));
+        assert!(!quiet_hours.contains(time(15, 0)));
+        assert!(!quiet_hours.contains(time(3, 0)));
+        Ok(())
+    }
+
+    #[test]
+    fn contains_wrapping_ok() -> Result<()> {
+        let quiet_hours: QuietHours = "22:00-07:00".parse()?;
+        assert!(quiet_hours.contains(time(23, 0)));
+        assert!(quiet_hours.contains(time(3, 0)));
+        assert!(!quiet_hours.contains(time(7, 0)));
+        assert!(!quiet_hours.contains(time(12, 0)));
+        Ok(())
+    }
+
+    #[test]
+    fn delivery_ok() {
+        assert_eq!(Severity::Low.delivery(false), Delivery::Now);
+        assert_eq!(Severity::Low.delivery(true), Delivery::Delayed);
+        assert_eq!(Severity::Normal.delivery(true), Delivery::Silently);
+        assert_eq!(Severity::Critical.delivery(true), Delivery::Now);
+    }
+}
@@ -0,0 +1,193 @@
+//! Readiness checks of the bot dependencies.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::time::Instant;
+
+use async_std::future::timeout;
+use fred::prelude::*;
+use fred::types::RedisKey;
+use rusty_shared_redis::Redis;
+use rusty_shared_telegram::api::BotApi;
+use rusty_shared_time::now;
+use rusty_shared_tractive::*;
+use serde::Serialize;
+
+use crate::listener::Listener;
+use crate::opts::ReadinessOpts;
+use crate::prelude::*;
+
+pub struct Readiness {
+    redis: Redis,
+    bot_api: BotApi,
+    opts: ReadinessOpts,
+
+    /// Consumer group of the listener.
+    group_name: String,
+
+    position_stream: RedisKey,
+    hardware_stream: RedisKey,
+
+    /// Unix time of the listener's most recent successful read, zero until there's one.
+    last_read_at: AtomicI64,
+}
+
+#[derive(Serialize)]
+pub struct Report {
+    pub is_ready: bool,
+    pub redis: RedisReport,
+    pub listener: AgeReport,
+    pub telegram: AgeReport,
+    pub streams: StreamsReport,
+}
+
+#[derive(Serialize)]
+pub struct RedisReport {
+    pub is_ok: bool,
+
+    /// `PING` round trip time.
+    pub latency_ms: Option<f64>,
+
+    pub error: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct AgeReport {
+    pub is_ok: bool,
+
+    /// Unix time of the most recent success.
+    pub last_success_at: Option<i64>,
+
+    /// Seconds since the most recent success.
+    pub age_secs: Option<i64>,
+}
+
+impl AgeReport {
+    fn new(last_success_at: Option<i64>, max_age_secs: i64) -> Self {
+        let age_secs = last_success_at.map(|last_success_at| now() - last_success_at);
+        Self {
+            is_ok: age_secs.is_some_and(|age_secs| age_secs <= max_age_secs),
+            last_success_at,
+            age_secs,
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct StreamsReport {
+    pub position: Option<GroupReport>,
+    pub hardware: Option<GroupReport>,
+}
+
+/// Consumer group state of a stream.
+#[derive(Serialize)]
+pub struct GroupReport {
+    /// Entries which have been delivered, but not acknowledged.
+    pub pending: Option<i64>,
+
+    /// Entries which have not been delivered yet, `None` when Redis can't tell.
+    pub lag: Option<i64>,
+}
+
+impl Readiness {
+    /// Timeout on each Redis call, so that the endpoint responds even when Redis is stuck.
+    const REDIS_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+
+    pub fn new(
+        redis: Redis,
+        bot_api: BotApi,
+        tracker_id: &str,
+        bot_user_id: i64,
+        opts: ReadinessOpts,
+    ) -> Self {
+        Self {
+            redis,
+            bot_api,
+            opts,
+            group_name: Listener::group_name(bot_user_id),
+            position_stream: position_stream_key(tracker_id),
+            hardware_stream: hardware_stream_key(tracker_id),
+            last_read_at: AtomicI64::new(0),
+        }
+    }
+
+    /// Called by the listener after each successful read.
+    pub fn on_read(&self) {
+        self.last_read_at.store(now(), Ordering::Relaxed);
+    }
+
+    #[instrument(skip_all)]
+    pub async fn check(&self) -> Report {
+        let redis = self.check_redis().await;
+        let listener = AgeReport::new(
+            Some(self.last_read_at.load(Ordering::Relaxed)).filter(|timestamp| *timestamp != 0),
+            self.opts.listener_max_age_secs,
+        );
+        let telegram =
+            AgeReport::new(self.bot_api.last_success_at(), self.opts.telegram_max_age_secs);
+        let streams = StreamsReport {
+            position: self.check_group(&self.position_stream).await,
+            hardware: self.check_group(&self.hardware_stream).await,
+        };
+        Report {
+            is_ready: redis.is_ok && listener.is_ok && telegram.is_ok,
+            redis,
+            listener,
+            telegram,
+            streams,
+        }
+    }
+
+    async fn check_redis(&self) -> RedisReport {
+        let start_time = Instant::now();
+        match timeout(Self::REDIS_TIMEOUT, self.redis.pool.ping()).await {
+            Ok(Ok(())) => RedisReport {
+                is_ok: true,
+                latency_ms: Some(start_time.elapsed().as_secs_f64() * 1000.0),
+                error: None,
+            },
+            Ok(Err(error)) => RedisReport {
+                is_ok: false,
+                latency_ms: None,
+                error: Some(error.to_string()),
+            },
+            Err(_) => RedisReport {
+                is_ok: false,
+                latency_ms: None,
+                error: Some("timed out".to_string()),
+            },
+        }
+    }
+
+    /// Look up the listener's consumer group, `None` if it's not available.
+    async fn check_group(&self, key: &RedisKey) -> Option<GroupReport> {
+        let groups = timeout(
+            Self::REDIS_TIMEOUT,
+            self.redis
+                .pool
+                .xinfo_groups::<Vec<HashMap<String, RedisValue>>, _>(key),
+        )
+        .await;
+        let groups = match groups {
+            Ok(Ok(groups)) => groups,
+            Ok(Err(error)) => {
+                warn!(?key, "failed to get the consumer groups: {:#}", error);
+                return None;
+            }
+            Err(_) => {
+                warn!(?key, "timed out while getting the consumer groups");
+                return None;
+            }
+        };
+        let group = groups.into_iter().find(|group| {
+            group
+                .get("name")
+                .and_then(RedisValue::as_str)
+                .is_some_and(|name| name == self.group_name)
+        })?;
+        Some(GroupReport {
+            pending: group.get("pending").and_then(RedisValue::as_i64),
+            lag: group.get("lag").and_then(RedisValue::as_i64),
+        })
+    }
+}
@@ -0,0 +1,121 @@
+//! Hot reload of the notification settings.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use fred::prelude::*;
+use fred::types::RedisKey;
+use rusty_shared_opts::config::Config;
+use rusty_shared_opts::shutdown::Shutdown;
+use rusty_shared_redis::Redis;
+
+use crate::opts::{NotificationOpts, Opts};
+use crate::prelude::*;
+
+/// Value which gets atomically replaced at runtime.
+pub struct Swappable<T>(RwLock<Arc<T>>);
+
+impl<T> Swappable<T> {
+    pub fn new(value: T) -> Self {
+        Self(RwLock::new(Arc::new(value)))
+    }
+
+    /// Take the current value, it stays the same for the caller even if it's swapped meanwhile.
+    pub fn load(&self) -> Arc<T> {
+        self.0.read().unwrap().clone()
+    }
+
+    pub fn store(&self, value: T) {
+        *self.0.write().unwrap() = Arc::new(value);
+    }
+}
+
+/// Watches the configuration file and the Redis hash, and swaps the notification settings.
+pub struct ConfigReloader {
+    redis: Redis,
+    config_path: Option<PathBuf>,
+
+    /// Hash of the settings, which override the configuration file.
+    hash_key: RedisKey,
+
+    interval: Duration,
+    opts: Arc<Swappable<NotificationOpts>>,
+}
+
+impl ConfigReloader {
+    pub fn new(
+        redis: Redis,
+        config_path: Option<PathBuf>,
+        tracker_id: &str,
+        bot_user_id: i64,
+        interval: Duration,
+        opts: Arc<Swappable<NotificationOpts>>,
+    ) -> Self {
+        Self {
+            redis,
+            config_path,
+            hash_key: RedisKey::from(format!(
+                "rusty:tractive:{}:telegram:{}:config",
+                tracker_id, bot_user_id,
+            )),
+            interval,
+            opts,
+        }
+    }
+
+    pub async fn run(self, shutdown: &Shutdown) -> Result<()> {
+        info!(hash_key = ?self.hash_key.as_str(), "watching the configuration…");
+        // The running settings have been parsed from the file on start, without the overrides.
+        // So, they only get swapped when the file changes, or the hash is not empty.
+        let mut last_config = self.read_file().ok();
+        while !shutdown.is_requested() {
+            match self.read_config().await {
+                Ok(config) if last_config.as_ref() != Some(&config) => {
+                    self.apply(&config);
+                    // Even when rejected, so that it's not reported over and over again.
+                    last_config = Some(config);
+                }
+                Ok(_) => trace!("the configuration is unchanged"),
+                Err(error) => {
+                    error!(
+                        "failed to read the configuration, keeping the running one: {:#}",
+                        error
+                    );
+                }
+            }
+            shutdown.sleep(self.interval).await;
+        }
+        Ok(())
+    }
+
+    fn read_file(&self) -> Result<Config> {
+        match &self.config_path {
+            Some(path) => Config::read(path),
+            None => Ok(Config::default()),
+        }
+    }
+
+    async fn read_config(&self) -> Result<Config> {
+        let mut config = self.read_file()?;
+        let overrides: HashMap<String, String> = self.redis.pool.hgetall(&self.hash_key).await?;
+        config.extend(overrides);
+        Ok(config)
+    }
+
+    fn apply(&self, config: &Config) {
+        match config
+            .try_parse::<Opts>()
+            .and_then(|opts| opts.service.notifications.validate().map(|_| opts))
+        {
+            Ok(opts) => {
+                self.opts.store(opts.service.notifications);
+                info!("🔄 reloaded the notification settings");
+            }
+            Err(error) => {
+                error!("rejected the new configuration, keeping the running one: {:#}", error);
+            }
+        }
+    }
+}
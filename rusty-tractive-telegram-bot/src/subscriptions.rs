@@ -0,0 +1,102 @@
+//! Chats which are subscribed to the tracker updates, and their settings.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use fred::prelude::*;
+use fred::types::RedisKey;
+use rusty_shared_redis::Redis;
+
+use crate::prelude::*;
+
+#[derive(Clone)]
+pub struct Subscriptions {
+    redis: Redis,
+
+    /// Set of the subscribed chat IDs.
+    chat_ids_key: RedisKey,
+
+    /// Hash of the chat locales by chat ID, which override the default one.
+    chat_locales_key: RedisKey,
+
+    /// Users who are allowed to subscribe and unsubscribe chats.
+    allowed_user_ids: Arc<Vec<i64>>,
+}
+
+impl Subscriptions {
+    pub fn new(
+        redis: Redis,
+        tracker_id: &str,
+        bot_user_id: i64,
+        allowed_user_ids: Vec<i64>,
+    ) -> Self {
+        let prefix = format!("rusty:tractive:{}:telegram:{}", tracker_id, bot_user_id);
+        Self {
+            redis,
+            chat_ids_key: RedisKey::from(format!("{}:chat_ids", prefix)),
+            chat_locales_key: RedisKey::from(format!("{}:chat_locales", prefix)),
+            allowed_user_ids: Arc::new(allowed_user_ids),
+        }
+    }
+
+    pub fn is_allowed(&self, user_id: i64) -> bool {
+        self.allowed_user_ids.contains(&user_id)
+    }
+
+    /// Subscribe the chat. Returns `false` if the chat is already subscribed.
+    #[instrument(skip(self))]
+    pub async fn subscribe(&self, chat_id: i64) -> Result<bool> {
+        let n_added = self
+            .redis
+            .pool
+            .sadd::<i64, _, _>(&self.chat_ids_key, chat_id)
+            .await?;
+        Ok(n_added != 0)
+    }
+
+    /// Unsubscribe the chat. Returns `false` if the chat has not been subscribed.
+    #[instrument(skip(self))]
+    pub async fn unsubscribe(&self, chat_id: i64) -> Result<bool> {
+        let n_removed = self
+            .redis
+            .pool
+            .srem::<i64, _, _>(&self.chat_ids_key, chat_id)
+            .await?;
+        Ok(n_removed != 0)
+    }
+
+    pub async fn is_subscribed(&self, chat_id: i64) -> Result<bool> {
+        Ok(self
+            .redis
+            .pool
+            .sismember(&self.chat_ids_key, chat_id)
+            .await?)
+    }
+
+    /// All the subscribed chat IDs.
+    pub async fn chat_ids(&self) -> Result<Vec<i64>> {
+        Ok(self.redis.pool.smembers(&self.chat_ids_key).await?)
+    }
+
+    pub async fn chat_locale(&self, chat_id: i64) -> Result<Option<String>> {
+        Ok(self
+            .redis
+            .pool
+            .hget(&self.chat_locales_key, chat_id)
+            .await?)
+    }
+
+    #[instrument(skip(self))]
+    pub async fn set_chat_locale(&self, chat_id: i64, locale: &str) -> Result<()> {
+        self.redis
+            .pool
+            .hset::<(), _, _>(&self.chat_locales_key, (chat_id, locale))
+            .await?;
+        Ok(())
+    }
+
+    /// Locales of all the chats which have one set.
+    pub async fn chat_locales(&self) -> Result<HashMap<i64, String>> {
+        Ok(self.redis.pool.hgetall(&self.chat_locales_key).await?)
+    }
+}
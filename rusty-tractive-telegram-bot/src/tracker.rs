@@ -0,0 +1,63 @@
+//! Reads the latest tracker state directly from the Tractive streams.
+
+use std::collections::HashMap;
+
+use fred::prelude::*;
+use fred::types::RedisKey;
+use rusty_shared_redis::Redis;
+use rusty_shared_tractive::*;
+
+use crate::prelude::*;
+
+#[derive(Clone)]
+pub struct Tracker {
+    redis: Redis,
+    position_stream: RedisKey,
+    hardware_stream: RedisKey,
+}
+
+impl Tracker {
+    /// Number of the hardware entries which is enough for the battery statistics.
+    pub const HARDWARE_HISTORY_COUNT: u64 = 1000;
+
+    pub fn new(redis: Redis, tracker_id: &str) -> Self {
+        Self {
+            redis,
+            position_stream: position_stream_key(tracker_id),
+            hardware_stream: hardware_stream_key(tracker_id),
+        }
+    }
+
+    /// The most recent position, if any.
+    #[instrument(skip_all)]
+    pub async fn last_position(&self) -> Result<Option<PositionEntry>> {
+        self.history(&self.position_stream, 1)
+            .await?
+            .pop()
+            .map(PositionEntry::try_from)
+            .transpose()
+            .context("failed to parse the position entry")
+    }
+
+    /// Up to `count` of the most recent hardware entries, newest first.
+    #[instrument(skip(self))]
+    pub async fn hardware_history(&self, count: u64) -> Result<Vec<HardwareEntry>> {
+        self.history(&self.hardware_stream, count)
+            .await?
+            .into_iter()
+            .map(HardwareEntry::try_from)
+            .collect::<Result<_, _>>()
+            .context("failed to parse the hardware entries")
+    }
+
+    /// Read up to `count` of the most recent stream entries, newest first.
+    async fn history(&self, key: &RedisKey, count: u64) -> Result<Vec<HashMap<String, String>>> {
+        let entries = self
+            .redis
+            .pool
+            .xrevrange_values::<String, String, String, _, _, _>(key, "+", "-", Some(count))
+            .await?;
+        debug!(n_entries = entries.len());
+        Ok(entries.into_iter().map(|(_, entry)| entry).collect())
+    }
+}
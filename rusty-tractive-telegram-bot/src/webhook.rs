@@ -0,0 +1,110 @@
+//! Periodically checks the webhook status as Telegram sees it.
+
+use std::sync::RwLock;
+use std::time;
+
+use rusty_shared_opts::shutdown::Shutdown;
+use rusty_shared_telegram::api::BotApi;
+use rusty_shared_telegram::methods::{GetWebhookInfo, Method};
+use rusty_shared_telegram::models::WebhookInfo;
+
+use crate::prelude::*;
+
+pub struct WebhookMonitor {
+    bot_api: BotApi,
+
+    /// Webhook URL which is expected to be set.
+    webhook_url: String,
+
+    interval: time::Duration,
+
+    /// Number of the pending updates, at which a warning gets logged.
+    pending_update_threshold: u32,
+
+    /// Whether `setWebhook` should drop the pending updates.
+    drop_pending_updates: bool,
+
+    /// The most recent `getWebhookInfo` result.
+    last_info: RwLock<Option<WebhookInfo>>,
+}
+
+impl WebhookMonitor {
+    pub const fn new(
+        bot_api: BotApi,
+        webhook_url: String,
+        interval: time::Duration,
+        pending_update_threshold: u32,
+        drop_pending_updates: bool,
+    ) -> Self {
+        Self {
+            bot_api,
+            webhook_url,
+            interval,
+            pending_update_threshold,
+            drop_pending_updates,
+            last_info: RwLock::new(None),
+        }
+    }
+
+    pub async fn run(&self, shutdown: &Shutdown) -> Result<()> {
+        info!(interval = ?self.interval, "running the webhook monitor…");
+        loop {
+            if let Err(error) = self.check().await {
+                warn!("failed to check the webhook: {:#}", error);
+            }
+            if !shutdown.sleep(self.interval).await {
+                return Ok(());
+            }
+        }
+    }
+
+    pub fn webhook_url(&self) -> &str {
+        &self.webhook_url
+    }
+
+    pub const fn drop_pending_updates(&self) -> bool {
+        self.drop_pending_updates
+    }
+
+    /// The most recent webhook information, if any.
+    pub fn last_info(&self) -> Option<WebhookInfo> {
+        self.last_info.read().unwrap().clone()
+    }
+
+    #[instrument(skip_all)]
+    async fn check(&self) -> Result<()> {
+        let info = GetWebhookInfo.call(&self.bot_api).await?;
+        debug!(
+            info.pending_update_count,
+            ?info.last_error_date,
+            ?info.last_error_message,
+            "webhook info",
+        );
+
+        if info.url != self.webhook_url {
+            warn!(info.url, expected_url = self.webhook_url, "🙅 unexpected webhook URL");
+        }
+
+        if info.pending_update_count >= self.pending_update_threshold {
+            warn!(info.pending_update_count, "📬 the updates are piling up");
+        }
+
+        let last_error_date = self
+            .last_info
+            .read()
+            .unwrap()
+            .as_ref()
+            .and_then(|last_info| last_info.last_error_date);
+        if info.last_error_date.is_some() && info.last_error_date != last_error_date {
+            warn!(
+                info.pending_update_count,
+                ?info.last_error_date,
+                ?info.last_error_message,
+                "🙅 Telegram reports a webhook delivery error",
+            );
+        }
+
+        *self.last_info.write().unwrap() = Some(info);
+        Ok(())
+    }
+}
@@ -1,5 +1,3 @@
-use std::io::ErrorKind;
-
 use anyhow::{Context, Error, Result};
 use futures::{AsyncBufReadExt, Stream, TryStreamExt};
 use reqwest::header::{HeaderMap, HeaderValue};
@@ -78,7 +76,7 @@ impl Api {
             .error_for_status()
             .context("the channel request failed")?
             .bytes_stream()
-            .map_err(|error| std::io::Error::new(ErrorKind::Other, error))
+            .map_err(std::io::Error::other)
             .into_async_read()
             .lines()
             .try_filter_map(|line| async move {
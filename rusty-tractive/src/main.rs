@@ -8,14 +8,21 @@
     clippy::needless_pass_by_value
 )]
 
+use std::sync::Arc;
+use std::time::Duration;
+
 use anyhow::Result;
-use clap::Parser;
+use futures::future::try_join3;
+use rusty_shared_heartbeat::status::ServiceStatus;
+use rusty_shared_heartbeat::Heartbeat;
+use rusty_shared_opts::systemd;
 
 use crate::api::Api;
 use crate::opts::Opts;
 use crate::service::Service;
 
 mod api;
+mod metrics;
 mod models;
 mod opts;
 mod service;
@@ -24,14 +31,41 @@ static BIN_NAME: &str = env!("CARGO_BIN_NAME");
 
 #[async_std::main]
 async fn main() -> Result<()> {
-    let opts: Opts = Opts::parse();
-    let _guard = rusty_shared_tracing::init(opts.sentry, BIN_NAME)?;
+    let opts: Opts = rusty_shared_opts::config::parse()?;
+    let _guard = rusty_shared_tracing::init(opts.sentry, opts.log, opts.otlp, BIN_NAME)?;
+    let shutdown = opts.shutdown.install()?;
+    // Before anything else, so that the startup failures get reported too.
+    let heartbeat = Heartbeat::new(&opts.heartbeat)?;
 
-    let service = Service {
-        api: Api::new()?,
-        redis: rusty_shared_redis::Redis::connect(&opts.redis.redis_url, BIN_NAME).await?,
-        heartbeat: opts.heartbeat.get_heartbeat()?,
-        opts: opts.service,
+    let service_future = async {
+        systemd::notify_status("connecting to Redis");
+        let redis = rusty_shared_redis::Redis::connect(&opts.redis.redis_url, BIN_NAME).await?;
+        let status = Arc::new(ServiceStatus::new(
+            redis.clone(),
+            BIN_NAME,
+            env!("CARGO_PKG_VERSION"),
+            Duration::from_secs(opts.heartbeat.status_ttl_secs),
+        ));
+        let service = Service {
+            api: Api::new()?,
+            redis,
+            heartbeat: heartbeat.clone().with_status(status.clone()),
+            opts: opts.service,
+        };
+        let metrics_future = async {
+            match &opts.metrics.metrics_bind_endpoint {
+                Some(bind_endpoint) => {
+                    rusty_shared_metrics::serve(bind_endpoint, shutdown.requested()).await
+                }
+                None => Ok(()),
+            }
+        };
+        try_join3(service.run(&shutdown), status.run(&shutdown), metrics_future).await?;
+        Ok(())
     };
-    service.run().await
+    let result = shutdown.run(service_future).await;
+    if let Err(error) = &result {
+        heartbeat.fail(error).await;
+    }
+    result
 }
@@ -0,0 +1,38 @@
+use once_cell::sync::Lazy;
+use rusty_shared_metrics::*;
+
+pub static CHANNEL_CONNECTIONS: Lazy<IntCounter> = Lazy::new(|| {
+    register_int_counter!(
+        "rusty_tractive_channel_connections_total",
+        "Connections to the Tractive channel, including the reconnects"
+    )
+    .unwrap()
+});
+
+pub static CHANNEL_MESSAGES: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "rusty_tractive_channel_messages_total",
+        "Tractive channel messages by type",
+        &["type"],
+    )
+    .unwrap()
+});
+
+pub static STREAM_ENTRIES: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "rusty_tractive_stream_entries_total",
+        "Entries written to the Redis streams",
+        &["stream"],
+    )
+    .unwrap()
+});
+
+/// Updates which are skipped, because their timestamp is not newer than the last one.
+pub static DEDUP_SKIPS: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "rusty_tractive_dedup_skips_total",
+        "Duplicate or outdated updates which were not written to the streams",
+        &["stream"],
+    )
+    .unwrap()
+});
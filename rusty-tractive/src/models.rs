@@ -32,9 +32,22 @@ pub enum Message {
     Other,
 }
 
+impl Message {
+    /// Message type for the metrics.
+    pub const fn type_name(&self) -> &'static str {
+        match self {
+            Self::Handshake(_) => "handshake",
+            Self::KeepAlive(_) => "keep-alive",
+            Self::TrackerStatus(_) => "tracker_status",
+            Self::Other => "other",
+        }
+    }
+}
+
 #[serde_as]
 #[derive(Debug, Deserialize)]
 pub struct HandshakeMessage {
+    #[allow(dead_code)]
     pub channel_id: String,
 
     #[serde_as(as = "DurationSeconds<u64>")]
@@ -43,6 +56,7 @@ pub struct HandshakeMessage {
 
 #[derive(Debug, Deserialize)]
 pub struct KeepAliveMessage {
+    #[allow(dead_code)]
     #[serde(rename = "channelId")]
     pub channel_id: String,
 
@@ -58,11 +72,34 @@ pub struct TrackerStatusMessage {
     pub tracker_id: String,
     pub hardware: Option<HardwareEntry>,
     pub position: Option<Position>,
+    #[allow(dead_code)]
     pub live_tracking: Option<LiveTracking>,
+    pub charging_state: Option<ChargingState>,
+}
+
+#[derive(Debug, Deserialize, Copy, Clone, PartialEq, Eq)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ChargingState {
+    Charging,
+    NotCharging,
+
+    #[serde(other)]
+    Other,
+}
+
+impl ChargingState {
+    pub const fn is_charging(self) -> Option<bool> {
+        match self {
+            Self::Charging => Some(true),
+            Self::NotCharging => Some(false),
+            Self::Other => None,
+        }
+    }
 }
 
 #[derive(Debug, Deserialize)]
 pub struct LiveTracking {
+    #[allow(dead_code)]
     #[serde(rename = "active")]
     pub is_active: bool,
 }
@@ -141,6 +178,7 @@ mod tests {
         match message {
             Message::TrackerStatus(message) => {
                 assert_eq!(message.tracker_id, "CENSORED");
+                assert_eq!(message.charging_state, Some(ChargingState::NotCharging));
                 Ok(())
             }
             _ => bail!("incorrect message type: {:?}", message),
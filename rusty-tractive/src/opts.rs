@@ -1,18 +1,33 @@
 use clap::Parser;
-use rusty_shared_opts::{heartbeat, redis, sentry};
+use rusty_shared_opts::{config, heartbeat, log, metrics, otlp, redis, sentry, shutdown};
 
 #[derive(Parser)]
 #[clap(author, version, about)]
 pub struct Opts {
+    #[clap(flatten)]
+    pub config: config::Opts,
+
     #[clap(flatten)]
     pub redis: redis::Opts,
 
     #[clap(flatten)]
     pub sentry: sentry::Opts,
 
+    #[clap(flatten)]
+    pub log: log::Opts,
+
+    #[clap(flatten)]
+    pub otlp: otlp::Opts,
+
     #[clap(flatten)]
     pub heartbeat: heartbeat::Opts,
 
+    #[clap(flatten)]
+    pub metrics: metrics::Opts,
+
+    #[clap(flatten)]
+    pub shutdown: shutdown::Opts,
+
     #[clap(flatten)]
     pub service: ServiceOpts,
 }
@@ -1,18 +1,22 @@
 use std::collections::HashMap;
 use std::time;
 
-use anyhow::{bail, Context, Result};
+use anyhow::{Context, Result};
 use async_std::future::timeout;
 use fred::prelude::*;
-use futures::StreamExt;
+use futures::{Stream, StreamExt};
 use kv_derive::prelude::*;
-use rusty_shared_opts::heartbeat::Heartbeat;
+use rusty_shared_heartbeat::Heartbeat;
+use rusty_shared_opts::shutdown::Shutdown;
+use rusty_shared_opts::systemd::{notify_ready, notify_status};
 use rusty_shared_redis::Redis;
+use rusty_shared_tracing::propagation::inject_context;
 use rusty_shared_tractive::{
     hardware_stream_key, position_stream_key, HardwareEntry, PositionEntry,
 };
-use tracing::{debug, info, instrument};
+use tracing::{debug, info, instrument, warn};
 
+use crate::metrics::*;
 use crate::models::*;
 use crate::opts::ServiceOpts;
 use crate::Api;
@@ -25,17 +29,64 @@ pub struct Service {
 }
 
 impl Service {
-    pub async fn run(&self) -> Result<()> {
-        let (user_id, access_token) = self
-            .get_authentication()
-            .await
-            .context("failed to authenticate")?;
+    /// Delay before reconnecting, once Tractive closes the channel.
+    const RECONNECT_DELAY: time::Duration = time::Duration::from_secs(5);
+
+    /// Run the service until the shutdown is requested, which closes the channel.
+    ///
+    /// The channel gets reopened, whenever Tractive closes it.
+    pub async fn run(&self, shutdown: &Shutdown) -> Result<()> {
+        loop {
+            notify_status("authenticating");
+            let (user_id, access_token) = self
+                .get_authentication()
+                .await
+                .context("failed to authenticate")?;
+
+            let messages = self.api.get_messages(&user_id, &access_token).await?;
+            CHANNEL_CONNECTIONS.inc();
+            notify_ready("listening to the Tractive channel");
+            if !self.listen(messages, shutdown).await? {
+                info!("closing the channel…");
+                return Ok(());
+            }
+
+            warn!(delay = ?Self::RECONNECT_DELAY, "the channel has been closed, reconnecting…");
+            notify_status("reconnecting to the Tractive channel");
+            if !shutdown.sleep(Self::RECONNECT_DELAY).await {
+                return Ok(());
+            }
+        }
+    }
 
-        let mut messages = Box::pin(self.api.get_messages(&user_id, &access_token).await?);
+    /// Handle the channel messages.
+    ///
+    /// Returns `true` when the channel has been closed by Tractive,
+    /// and `false` when the shutdown is requested.
+    async fn listen(
+        &self,
+        messages: impl Stream<Item = Result<Message>>,
+        shutdown: &Shutdown,
+    ) -> Result<bool> {
+        let mut messages = Box::pin(messages);
         let mut keep_alive_ttl = time::Duration::from_secs(600);
 
-        while let Some(message) = timeout(keep_alive_ttl, messages.next()).await? {
-            match message? {
+        loop {
+            let message = match shutdown
+                .until(timeout(keep_alive_ttl, messages.next()))
+                .await
+            {
+                Some(message) => message?,
+                None => return Ok(false),
+            };
+            let message = match message {
+                Some(message) => message?,
+                None => return Ok(true),
+            };
+            CHANNEL_MESSAGES
+                .with_label_values(&[message.type_name()])
+                .inc();
+            match message {
                 Message::Handshake(payload) => {
                     info!(keep_alive_ttl = ?payload.keep_alive_ttl, "🐈 meow!");
                     keep_alive_ttl = payload.keep_alive_ttl;
@@ -50,8 +101,6 @@ impl Service {
             };
             self.heartbeat.send().await;
         }
-
-        bail!("the message stream has ended unexpectedly");
     }
 
     #[tracing::instrument(skip_all, fields(self.email = ?self.opts.email))]
@@ -83,18 +132,19 @@ impl Service {
             ("access_token", &token.access_token),
         ];
         let transaction = self.redis.pool.multi(true).await?;
-        transaction.hset(key, values).await?;
+        transaction.hset::<(), _, _>(key, values).await?;
         transaction
-            .expire_at(key, token.expires_at.timestamp())
+            .expire_at::<(), _>(key, token.expires_at.timestamp())
             .await?;
-        transaction.exec().await?;
+        transaction.exec::<()>().await?;
         Ok(())
     }
 
     #[instrument(skip_all, fields(tracker_id = ?payload.tracker_id))]
     async fn on_tracker_status(&self, payload: TrackerStatusMessage) -> Result<()> {
         let tracker_id = payload.tracker_id.to_lowercase();
-        if let Some(hardware) = payload.hardware {
+        if let Some(mut hardware) = payload.hardware {
+            hardware.is_charging = payload.charging_state.and_then(ChargingState::is_charging);
             self.on_hardware_update(&tracker_id, hardware).await?;
         }
         if let Some(position) = payload.position {
@@ -106,7 +156,12 @@ impl Service {
 
     #[instrument(skip_all)]
     async fn on_hardware_update(&self, tracker_id: &str, hardware: HardwareEntry) -> Result<()> {
-        info!(timestamp = ?hardware.timestamp, battery_level = hardware.battery_level, "⌚ hardware update️");
+        info!(
+            timestamp = ?hardware.timestamp,
+            battery_level = hardware.battery_level,
+            is_charging = ?hardware.is_charging,
+            "⌚ hardware update️",
+        );
         let (is_timestamp_updated, _) = self
             .redis
             .set_if_greater(
@@ -117,20 +172,19 @@ impl Service {
             .context("failed to update the last hardware timestamp")?;
         if !is_timestamp_updated {
             info!("⌚ timestamp is not updated");
+            DEDUP_SKIPS.with_label_values(&["hardware"]).inc();
             return Ok(());
         }
         info!("⌚ pushing new entry…");
+        let entry_id = format!("{}-0", hardware.timestamp.timestamp_millis());
+        let mut fields = hardware.into_vec();
+        fields.extend(inject_context());
         self.redis
             .pool
-            .xadd(
-                hardware_stream_key(tracker_id),
-                false,
-                None,
-                format!("{}-0", hardware.timestamp.timestamp_millis()),
-                hardware.into_vec(),
-            )
+            .xadd::<(), _, _, _, _>(hardware_stream_key(tracker_id), false, None, entry_id, fields)
             .await
             .context("failed to push the hardware stream entry")?;
+        STREAM_ENTRIES.with_label_values(&["hardware"]).inc();
         Ok(())
     }
 
@@ -155,20 +209,19 @@ impl Service {
             .context("failed to update the last position timestamp")?;
         if !is_timestamp_updated {
             info!("🎯 timestamp is not updated");
+            DEDUP_SKIPS.with_label_values(&["position"]).inc();
             return Ok(());
         }
         info!("🎯 pushing new entry…");
+        let entry_id = format!("{}-0", position.timestamp.timestamp_millis());
+        let mut fields = PositionEntry::from(position).into_vec();
+        fields.extend(inject_context());
         self.redis
             .pool
-            .xadd(
-                position_stream_key(tracker_id),
-                false,
-                None,
-                format!("{}-0", position.timestamp.timestamp_millis()),
-                PositionEntry::from(position).into_vec(),
-            )
+            .xadd::<(), _, _, _, _>(position_stream_key(tracker_id), false, None, entry_id, fields)
             .await
             .context("failed to push the position stream entry")?;
+        STREAM_ENTRIES.with_label_values(&["position"]).inc();
         Ok(())
     }
 }